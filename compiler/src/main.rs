@@ -1,98 +1,163 @@
-use flat_html::{Element, FlatHtml, TagKind};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
-use swb_compiler::compile;
+use std::time::{SystemTime, UNIX_EPOCH};
+use swb_compiler::html::{self, Element, TagKind};
+use swb_compiler::{compile_elements, CodeEncoding, CompileOptions, TextCompression};
 
-use anyhow::Result;
-use less_html::strip::ElementIter;
-use less_html::Document;
+use anyhow::{anyhow, Result};
 
-fn strip(next: &Element, it: &mut ElementIter) -> Option<Vec<Element>> {
-    // We split our text on newlines, and delete any lines that are only whitespace
-    if let Element::Text(str) = next {
-        
+/// Widest table that `--text-tables` renders as text, the same width text is split at
+const TABLE_TEXT_WIDTH: usize = 50;
 
-        return Some(str.split_terminator('\n')
-                .flat_map(|line| {
-                    if line.chars().all(|c| c.is_whitespace()) {
-                        None
-                    } else {
-                        // Trim leading and trailing whitespace
-                        Some(Element::Text(line.trim().to_string()))
-                    }
-                })
-                .flat_map(|txt| {
-                    let Element::Text(str) = txt else { unreachable!() };
-                    str
-                        .chars()
-                        .collect::<Vec<char>>()
-                        .chunks(50)
-                        .map(|s| Element::Text(s.iter().collect::<String>()))
-                        .collect::<Vec<_>>()
-                })
-                .collect(),
-        );
-    }  
+/// Images are scaled down to fit the width of the screen, unless `--image-width` says otherwise
+const IMAGE_MAX_WIDTH: u32 = 400;
 
-    match next {
-        Element::Tag(TagKind::Script) => {
-            while let Some(child) = it.next() {
-                if let Element::EndTag(TagKind::Script) = *child {
-                    return None;
-                }
+fn strip(elements: Vec<Element>) -> Vec<Element> {
+    let mut stripped = vec![];
+    // Number of open <pre>s, text inside of them is kept as is
+    let mut preformatted = 0usize;
+    for element in elements {
+        match element {
+            Element::Tag(ref tag) if tag.kind == TagKind::Preformatted => {
+                preformatted += 1;
+                stripped.push(element);
             }
-        }
-        _ => {}
-    };
-
-    // Leave IgnoreTag alone, this is only used while stripping
-    less_html::keep_unit_element!(IgnoreTag, next);
-    // Leave EndTag alone
-    less_html::keep_element!(EndTag, next);
-    // Leave regular tags alone
-    less_html::keep_element!(Tag, next);
-
-    match next {
-        Element::LineBreak => {
-            // Collapse all subsequent linebreaks into one.
-            while let Some(Element::LineBreak) = it.peek() {
-                let _ = it.next();
+            Element::EndTag(TagKind::Preformatted) => {
+                preformatted = preformatted.saturating_sub(1);
+                stripped.push(element);
             }
-            return Some(vec![Element::LineBreak]);
-        }
-        // All other cases were already handled
-        _ => {
-            unreachable!()
+            Element::Text(_) if preformatted > 0 => stripped.push(element),
+            // We split our text on newlines, and delete any lines that are only whitespace
+            Element::Text(str) => stripped.extend(
+                str.split_terminator('\n')
+                    // Trim leading and trailing whitespace
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .flat_map(|line| {
+                        line.chars()
+                            .collect::<Vec<char>>()
+                            .chunks(50)
+                            .map(|s| Element::Text(s.iter().collect::<String>()))
+                            .collect::<Vec<_>>()
+                    }),
+            ),
+            // Collapse all subsequent linebreaks into one. Paragraphs and other blocks are kept
+            // apart by their own instructions, not by empty lines.
+            Element::LineBreak if stripped.last() == Some(&Element::LineBreak) => {}
+            element => stripped.push(element),
         }
-    };
+    }
+    stripped
+}
+
+/// When the page is compiled, in seconds since the unix epoch. Like other build tools, this can
+/// be fixed with `SOURCE_DATE_EPOCH` to get the same output every time.
+fn timestamp() -> Option<u64> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.parse().ok(),
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|time| time.as_secs()),
+    }
 }
 
-fn strip_page(input: &Path) -> Result<FlatHtml> {
-    let doc = Document::from_file(input)?;
-    let html = less_html::parse(&doc)?;
-    let stripped = less_html::strip::oracle_strip(html, &strip)?;
-    Ok(stripped)
+fn strip_page(input: &Path) -> Result<Vec<Element>> {
+    let source = std::fs::read_to_string(input)?;
+    Ok(strip(html::parse(&source)))
 }
 
 fn main() -> Result<()> {
     let args = std::env::args().collect::<Vec<_>>();
     if args.len() < 2 {
-        println!("usage: swb [input] [--text]");
+        println!("usage: swb [input] [--text] [--compact] [--compress] [--checksum] [--text-tables] [--no-timestamp] [--no-images] [--image-width N]");
+        println!("images are loaded from files next to the input, and scaled down to {IMAGE_MAX_WIDTH} pixels wide by default");
         std::process::exit(1);
     }
+    let has_flag = |flag: &str| args[2..].iter().any(|arg| arg == flag);
+    let flag_value = |flag: &str| {
+        args[2..]
+            .iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 3))
+    };
+    let image_width = match flag_value("--image-width") {
+        Some(width) => width.parse().map_err(|_| anyhow!("invalid image width {width:?}"))?,
+        None => IMAGE_MAX_WIDTH,
+    };
     let path = Path::new(&args[1]);
     let input = strip_page(path)?;
-    let output = compile(&input)?;
+    let options = CompileOptions::new()
+        .with_table_text_width(has_flag("--text-tables").then_some(TABLE_TEXT_WIDTH))
+        .with_image_dir(path.parent().map(Path::to_path_buf).filter(|_| !has_flag("--no-images")))
+        .with_image_max_width(Some(image_width))
+        .with_timestamp(timestamp().filter(|_| !has_flag("--no-timestamp")));
+    let output = compile_elements(&input, &options)?;
+    println!("saved {} bytes of text by sharing strings", output.1.text_bytes_saved);
+    for skipped in &output.1.skipped_images {
+        println!("skipped image {skipped}");
+    }
     let out_path = path.with_extension("swb");
     let mut file = File::create(&out_path)?;
 
-    if args.len() == 3 && args[2] == "--text" {
+    if has_flag("--text") {
         write!(&mut file, "{}", output)?;
     } else {
-        let binary = output.binary().into_byte_buffer();
+        let encoding = if has_flag("--compact") {
+            CodeEncoding::Compact
+        } else {
+            CodeEncoding::Fixed
+        };
+        let compression = if has_flag("--compress") {
+            TextCompression::Lzss
+        } else {
+            TextCompression::None
+        };
+        let binary = output
+            .binary()
+            .with_encoding(encoding)
+            .with_compression(compression)
+            .with_checksum(has_flag("--checksum"))
+            .into_byte_buffer();
         file.write(binary.as_slice())?;
     }
    
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swb_shared::{Instruction, StyleVar};
+
+    #[test]
+    fn test_strip_preformatted() {
+        let code = "fn main() {\n    println!(\"a line that is longer than fifty characters\");\n}";
+        let page = format!("<p>  some   text  </p><pre>{code}\n</pre>");
+        let stripped = strip(html::parse(&page));
+        assert!(stripped.contains(&Element::Text("some text".to_string())));
+        assert!(stripped.contains(&Element::Text(format!("{code}\n"))));
+
+        let output = compile_elements(&stripped, &CompileOptions::new()).unwrap();
+        let program = output.0;
+        let start = program
+            .code
+            .iter()
+            .position(|instruction| *instruction == Instruction::PreformattedStart)
+            .unwrap();
+        let pre = &program.code[start..start + 10];
+        assert_eq!(pre[0], Instruction::PreformattedStart);
+        assert_eq!(pre[1], Instruction::Push(StyleVar::Monospace));
+        assert_eq!(pre[8], Instruction::Pop(StyleVar::Monospace));
+        assert_eq!(pre[9], Instruction::PreformattedEnd);
+        // Every line comes out whole, with its indentation, followed by a line break
+        for (i, line) in code.lines().enumerate() {
+            let Instruction::Text(range) = pre[2 + i * 2] else {
+                panic!("expected text, got {:?}", pre[2 + i * 2]);
+            };
+            assert_eq!(program.text.get(range), Some(line));
+            assert_eq!(pre[3 + i * 2], Instruction::Endl);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use swb_shared::Anchor;
+
+/// Builds the anchor table of a program from the `id` attributes on a page.
+#[derive(Debug, Default)]
+pub struct AnchorTable {
+    anchors: Vec<Anchor>,
+    indices: HashMap<String, u32>,
+}
+
+impl AnchorTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an anchor at the given instruction, returning its index. Like browsers, only the
+    /// first element with an id counts, so this returns `None` for duplicates.
+    pub fn add(&mut self, name: &str, instruction: u32) -> Option<u32> {
+        if self.indices.contains_key(name) {
+            return None;
+        }
+        let index = self.anchors.len() as u32;
+        self.anchors.push(Anchor::Named {
+            name: name.to_string(),
+            instruction,
+        });
+        self.indices.insert(name.to_string(), index);
+        Some(index)
+    }
+
+    /// Returns the instruction the anchor with this name is at.
+    pub fn instruction(&self, name: &str) -> Option<u32> {
+        self.indices
+            .get(name)
+            .and_then(|index| self.anchors[*index as usize].instruction())
+    }
+
+    pub fn into_anchors(self) -> Vec<Anchor> {
+        self.anchors
+    }
+}
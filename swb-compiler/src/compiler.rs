@@ -1,30 +1,114 @@
-use anyhow::{anyhow, Error, Result};
-use ascii::{AsAsciiStr, AsciiString, FromAsciiError, IntoAsciiString};
-use flat_html::{Element, TagKind};
+use anyhow::{Error, Result};
+use crate::anchors::AnchorTable;
+use crate::data::DataSection;
+use crate::forms::{self, FormTable};
+use crate::html::{self, Element, Tag, TagKind};
+use crate::images::ImageTable;
+use crate::links::LinkTable;
+use crate::table;
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 use swb_shared::{
-    Address, AddressRange, BinaryInstruction, BinaryProgram, Instruction, Program, StyleVar,
-    ToBinary,
+    Align, BinaryInstruction, BinaryProgram, CodeEncoding, ControlKind, Instruction, ListKind,
+    MAX_HEADING_LEVEL, Metadata, Program, Rule, StyleVar, TextCompression, ToBinary,
 };
 
-fn stylevar_from_tag(tag: TagKind) -> Result<StyleVar> {
+fn stylevar_from_tag(tag: &TagKind) -> Option<StyleVar> {
     match tag {
-        TagKind::Bold => Ok(StyleVar::Bold),
-        TagKind::Italic => Ok(StyleVar::Italic),
-        _ => Err(anyhow!("could not parse tag into stylevar")),
+        TagKind::Bold => Some(StyleVar::Bold),
+        TagKind::Italic => Some(StyleVar::Italic),
+        TagKind::Underline => Some(StyleVar::Underline),
+        TagKind::Code => Some(StyleVar::Monospace),
+        TagKind::Strikethrough | TagKind::Deleted => Some(StyleVar::Strikethrough),
+        TagKind::Superscript => Some(StyleVar::Superscript),
+        TagKind::Subscript => Some(StyleVar::Subscript),
+        TagKind::Small => Some(StyleVar::Small),
+        TagKind::Big => Some(StyleVar::Large),
+        _ => None,
     }
 }
 
+/// Parses the value of an `align` attribute or a `text-align` property
+fn parse_align(value: &str) -> Option<Align> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "left" | "start" => Some(Align::Left),
+        "center" | "middle" => Some(Align::Center),
+        "right" | "end" => Some(Align::Right),
+        "justify" => Some(Align::Justify),
+        _ => None,
+    }
+}
+
+/// Finds the alignment in an inline style, like `color: red; text-align: center`. Only plain
+/// declarations are understood, the last one wins like in CSS.
+fn align_from_style(style: &str) -> Option<Align> {
+    style
+        .rsplit(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .filter(|(property, _)| property.trim().eq_ignore_ascii_case("text-align"))
+        .find_map(|(_, value)| parse_align(value.trim().trim_end_matches("!important")))
+}
+
+/// The alignment of an element. Like in browsers, `text-align` wins over the `align` attribute.
+fn alignment(tag: &Tag) -> Option<Align> {
+    tag.style
+        .as_deref()
+        .and_then(align_from_style)
+        .or_else(|| tag.align.as_deref().and_then(parse_align))
+        .or((tag.kind == TagKind::Center).then_some(Align::Center))
+}
+
+/// Clamps a heading level to the levels instructions can hold, so `<h7>` becomes `<h6>`
+fn heading_level(level: u8) -> u8 {
+    level.clamp(1, MAX_HEADING_LEVEL)
+}
+
+/// Fills in metadata from a `<meta>` tag, given its name (or property) and content
+fn metadata_from_tag(meta: &mut Metadata, name: &str, content: &str) {
+    match name {
+        "og:title" => meta.title = Some(content.to_string()),
+        "og:url" => meta.url = Some(content.to_string()),
+        "content-language" => {
+            meta.lang.get_or_insert_with(|| content.to_string());
+        }
+        _ => {}
+    }
+}
+
+/// Statistics gathered while compiling a page
+#[derive(Debug, Default, Clone)]
+pub struct CompilationStats {
+    /// Bytes of text that were shared with identical or containing strings
+    /// instead of being stored again.
+    pub text_bytes_saved: usize,
+    /// Images that couldn't be loaded from the image directory, each with the reason. Their
+    /// alt text is used instead.
+    pub skipped_images: Vec<String>,
+}
+
 #[derive(Debug)]
-pub struct CompilationOutput(pub Program);
+pub struct CompilationOutput(pub Program, pub CompilationStats);
 
 #[derive(Debug)]
 pub struct CompiledBinary(pub BinaryProgram);
 
 impl CompiledBinary {
+    pub fn with_encoding(self, encoding: CodeEncoding) -> Self {
+        CompiledBinary(self.0.with_encoding(encoding))
+    }
+
+    pub fn with_compression(self, compression: TextCompression) -> Self {
+        CompiledBinary(self.0.with_compression(compression))
+    }
+
+    pub fn with_checksum(self, checksum: bool) -> Self {
+        CompiledBinary(self.0.with_checksum(checksum))
+    }
+
     /// Returns a byte buffer with the resulting binary
-    /// The first 8 bytes of the buffer contain the length of the data section in bytes.
+    /// The buffer starts with a [`swb_shared::Header`], which records the format version,
+    /// followed by a table of the sections in the file.
     pub fn into_byte_buffer(mut self) -> Vec<u8> {
         self.0.into_byte_buffer()
     }
@@ -36,51 +120,725 @@ impl CompilationOutput {
     }
 }
 
-/// Compiles a flat, possibly reduced HTML representation to SWB
-pub fn compile(input: &flat_html::FlatHtml) -> Result<CompilationOutput> {
-    let mut output = CompilationOutput(Program {
-        text: AsciiString::new(),
-        code: vec![],
-    });
-
-    output.0.code = input
-        .0
-        .iter()
-        .flat_map(|element| {
-            match element {
-                Element::Tag(TagKind::LineBreak) => Some(Instruction::Endl),
-                Element::Text(data) => {
-                    let start = output.0.text.len();
-                    let ascii: String = data.chars().filter(|c| c.is_ascii()).collect();
-                    output.0.text += &ascii.into_ascii_string().unwrap();
-                    Some(Instruction::Text(AddressRange {
-                        base: Address(start as u32),
-                        range: data.len() as u32,
-                    }))
-                }
-                Element::Tag(kind) => {
-                    // Try to parse this tag into a style var, and if so add a push instruction
-                    match stylevar_from_tag(kind.clone()) {
-                        Ok(value) => Some(Instruction::Push(value)),
-                        Err(_) => None,
+/// Options for [`compile_with`]
+#[derive(Debug, Default, Clone)]
+pub struct CompileOptions {
+    /// Tables that fit in this many characters per line are pre-rendered as fixed-width text,
+    /// for readers that don't implement table layout. Other tables use table instructions.
+    pub table_text_width: Option<usize>,
+    /// Directory that `src` attributes of images are relative to. Without one, the alt text
+    /// of images is used instead.
+    pub image_dir: Option<PathBuf>,
+    /// Images wider than this many pixels are scaled down to it.
+    pub image_max_width: Option<u32>,
+    /// When the page was compiled, in seconds since the unix epoch. Left out by default, so
+    /// compiling the same page twice gives the same program.
+    pub timestamp: Option<u64>,
+}
+
+impl CompileOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_table_text_width(self, table_text_width: Option<usize>) -> Self {
+        Self { table_text_width, ..self }
+    }
+
+    pub fn with_image_dir(self, image_dir: Option<PathBuf>) -> Self {
+        Self { image_dir, ..self }
+    }
+
+    pub fn with_image_max_width(self, image_max_width: Option<u32>) -> Self {
+        Self { image_max_width, ..self }
+    }
+
+    pub fn with_timestamp(self, timestamp: Option<u64>) -> Self {
+        Self { timestamp, ..self }
+    }
+}
+
+/// State kept while compiling a page
+#[derive(Debug, Default)]
+struct Compiler {
+    data: DataSection,
+    code: Vec<Instruction>,
+    meta: Metadata,
+    // Text inside <title> goes into the metadata instead of the page
+    title: Option<String>,
+    in_title: bool,
+    links: LinkTable,
+    // Whether every open <a> started a link, anchors without a href don't
+    open_links: Vec<bool>,
+    images: ImageTable,
+    forms: FormTable,
+    anchors: AnchorTable,
+    // Number of open <blockquote>s
+    indent: u8,
+    // Number of open <pre>s
+    preformatted: u8,
+    // Alignment set by every open element, if it set one
+    aligns: Vec<Option<Align>>,
+}
+
+impl Compiler {
+    /// Adds the instructions for an element to the code
+    fn element(&mut self, element: &Element) {
+        match element {
+            Element::Tag(tag) => {
+                // Marks the position of the element, so fragment links can jump to it
+                if let Some(id) = &tag.id {
+                    let instruction = self.code.len() as u32;
+                    if let Some(index) = self.anchors.add(id, instruction) {
+                        self.code.push(Instruction::Anchor(index));
                     }
                 }
-                Element::EndTag(kind) => match stylevar_from_tag(kind.clone()) {
-                    Ok(value) => Some(Instruction::Pop(value)),
-                    Err(_) => None,
-                },
-                Element::LineBreak => Some(Instruction::Endl),
-                Element::IgnoreTag => None,
+                if !tag.kind.is_void() {
+                    let align = alignment(tag);
+                    self.aligns.push(align);
+                    self.code.extend(align.map(Instruction::PushAlign));
+                }
+                let instruction = self.start_tag(&tag.kind);
+                self.code.extend(instruction);
             }
-        })
-        .collect();
-    output.0.code.push(Instruction::Stop);
+            Element::EndTag(kind) => {
+                let instruction = self.end_tag(kind);
+                self.code.extend(instruction);
+                if !kind.is_void() {
+                    let align = self.aligns.pop().flatten();
+                    self.code.extend(align.map(Instruction::PopAlign));
+                }
+            }
+            Element::Text(text) if self.in_title => {
+                self.title.get_or_insert_with(String::new).push_str(text);
+            }
+            // Text inside buttons, text areas and selects belongs to the control
+            Element::Text(text) if self.forms.is_capturing() => self.forms.text(text),
+            Element::Text(text) if self.preformatted > 0 => self.preformatted_text(text),
+            Element::Text(text) => {
+                let range = self.data.intern(text);
+                self.code.push(Instruction::Text(range));
+            }
+            Element::LineBreak => self.code.push(Instruction::Endl),
+        }
+    }
+
+    /// Returns the instruction for a start tag, if it has one
+    fn start_tag(&mut self, kind: &TagKind) -> Option<Instruction> {
+        match kind {
+            TagKind::Title => {
+                self.in_title = true;
+                None
+            }
+            TagKind::Html { lang } => {
+                // The lang attribute wins over a content-language <meta> tag
+                if let Some(lang) = lang {
+                    self.meta.lang = Some(lang.clone());
+                }
+                None
+            }
+            TagKind::Meta { name, content } => {
+                metadata_from_tag(&mut self.meta, name, content);
+                None
+            }
+            TagKind::Anchor { href } => {
+                self.open_links.push(href.is_some());
+                href.as_ref().map(|href| Instruction::LinkStart(self.links.intern(href)))
+            }
+            TagKind::Heading(level) => Some(Instruction::PushHeading(heading_level(*level))),
+            TagKind::UnorderedList => Some(Instruction::ListStart(ListKind::Unordered)),
+            TagKind::OrderedList { start } => {
+                Some(Instruction::ListStart(ListKind::Ordered(start.unwrap_or(1))))
+            }
+            // Items, rows and cells are only marked where they start
+            TagKind::ListItem => Some(Instruction::ListItem),
+            TagKind::Table => Some(Instruction::TableStart),
+            TagKind::TableRow => Some(Instruction::TableRow),
+            TagKind::TableData => Some(Instruction::TableCell),
+            TagKind::TableHeader => Some(Instruction::TableHeaderCell),
+            TagKind::Image { src, alt } => {
+                // Images that can't be loaded are replaced by their alt text, if there is any
+                match src.as_deref().and_then(|src| self.images.intern(src)) {
+                    Some(index) => Some(Instruction::Image(index)),
+                    None => alt
+                        .as_deref()
+                        .map(str::trim)
+                        .filter(|alt| !alt.is_empty())
+                        .map(|alt| Instruction::Text(self.data.intern(alt))),
+                }
+            }
+            TagKind::Form { action, method } => {
+                self.forms.start_form(action.as_deref(), method.as_deref());
+                None
+            }
+            TagKind::Input {
+                kind,
+                name,
+                value,
+                checked,
+            } => {
+                let kind = forms::input_kind(kind.as_deref())?;
+                let value = match (value.as_deref(), kind) {
+                    (Some(value), _) => value,
+                    (None, ControlKind::Checkbox | ControlKind::Radio) => "on",
+                    (None, ControlKind::Submit) => "Submit",
+                    (None, _) => "",
+                };
+                let index = self.forms.add(kind, name.as_deref(), value, *checked)?;
+                forms::control_instruction(kind, index)
+            }
+            TagKind::Button { kind, name, value } => {
+                // Only submit buttons do anything without scripts
+                if kind.as_deref().is_some_and(|kind| !kind.eq_ignore_ascii_case("submit")) {
+                    return None;
+                }
+                let index = self.forms.add(ControlKind::Submit, name.as_deref(), "", false)?;
+                self.forms.capture(index, Some(value.as_deref().unwrap_or("Submit")));
+                Some(Instruction::Submit(index))
+            }
+            TagKind::TextArea { name } => {
+                let index = self.forms.add(ControlKind::TextInput, name.as_deref(), "", false)?;
+                self.forms.capture(index, None);
+                Some(Instruction::TextInput(index))
+            }
+            TagKind::Select { name } => {
+                let index = self.forms.add(ControlKind::Select, name.as_deref(), "", false)?;
+                self.forms.capture(index, None);
+                Some(Instruction::Select(index))
+            }
+            TagKind::SelectOption { value, selected } => {
+                self.forms.add_option(value.as_deref(), *selected);
+                None
+            }
+            TagKind::HorizontalRule => Some(Instruction::Rule(Rule::default())),
+            TagKind::Paragraph => Some(Instruction::ParagraphStart),
+            TagKind::Div | TagKind::Section | TagKind::Center => Some(Instruction::BlockBreak),
+            TagKind::BlockQuote => {
+                self.indent = self.indent.saturating_add(1);
+                Some(Instruction::Indent(self.indent))
+            }
+            TagKind::Preformatted => {
+                self.preformatted = self.preformatted.saturating_add(1);
+                self.code.push(Instruction::PreformattedStart);
+                Some(Instruction::Push(StyleVar::Monospace))
+            }
+            kind => stylevar_from_tag(kind).map(Instruction::Push),
+        }
+    }
 
-    Ok(output)
+    /// Returns the instruction for an end tag, if it has one
+    fn end_tag(&mut self, kind: &TagKind) -> Option<Instruction> {
+        match kind {
+            TagKind::Title => {
+                self.in_title = false;
+                None
+            }
+            TagKind::Anchor { .. } => match self.open_links.pop() {
+                Some(true) => Some(Instruction::LinkEnd),
+                _ => None,
+            },
+            TagKind::Heading(level) => Some(Instruction::PopHeading(heading_level(*level))),
+            TagKind::UnorderedList | TagKind::OrderedList { .. } => Some(Instruction::ListEnd),
+            TagKind::Table => Some(Instruction::TableEnd),
+            TagKind::Form { .. } => {
+                self.forms.end_form();
+                None
+            }
+            TagKind::Button { .. } | TagKind::TextArea { .. } | TagKind::Select { .. } => {
+                self.forms.end_capture();
+                None
+            }
+            TagKind::Paragraph => Some(Instruction::ParagraphEnd),
+            TagKind::Div | TagKind::Section | TagKind::Center => Some(Instruction::BlockBreak),
+            TagKind::BlockQuote => {
+                self.indent = self.indent.saturating_sub(1);
+                Some(Instruction::Indent(self.indent))
+            }
+            TagKind::Preformatted => {
+                self.preformatted = self.preformatted.saturating_sub(1);
+                self.code.push(Instruction::Pop(StyleVar::Monospace));
+                Some(Instruction::PreformattedEnd)
+            }
+            kind => stylevar_from_tag(kind).map(Instruction::Pop),
+        }
+    }
+
+    /// Adds lines of text that must be shown as they are
+    fn lines(&mut self, lines: &[String]) {
+        for line in lines {
+            if !line.is_empty() {
+                self.code.push(Instruction::Text(self.data.intern(line)));
+            }
+            self.code.push(Instruction::Endl);
+        }
+    }
+
+    /// Adds text inside a <pre>, keeping its whitespace and starting a new line at every newline
+    fn preformatted_text(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.code.push(Instruction::Endl);
+            }
+            if !line.is_empty() {
+                self.code.push(Instruction::Text(self.data.intern(line)));
+            }
+        }
+    }
+
+    fn finish(mut self) -> CompilationOutput {
+        self.code.push(Instruction::Stop);
+        let stats = CompilationStats {
+            text_bytes_saved: self.data.bytes_saved(),
+            skipped_images: self.images.skipped().to_vec(),
+        };
+
+        // The <title> is preferred over the og:title <meta> tag
+        let title = self.title.map(|title| title.trim().to_string());
+        if let Some(title) = title.filter(|title| !title.is_empty()) {
+            self.meta.title = Some(title);
+        }
+        self.meta.compiler = Some(format!("swb-compiler {}", env!("CARGO_PKG_VERSION")));
+
+        let mut links = self.links;
+        links.resolve_fragments(&self.anchors);
+        let program = Program {
+            text: self.data.into_text(),
+            code: self.code,
+            meta: self.meta,
+            links: links.into_links(),
+            images: self.images.into_images(),
+            forms: self.forms.into_forms(),
+            anchors: self.anchors.into_anchors(),
+            ..Default::default()
+        };
+        CompilationOutput(program, stats)
+    }
+}
+
+/// Compiles an HTML page to SWB
+pub fn compile(input: &str) -> Result<CompilationOutput> {
+    compile_with(input, &CompileOptions::default())
+}
+
+/// Compiles an HTML page to SWB, see [`CompileOptions`]
+pub fn compile_with(input: &str, options: &CompileOptions) -> Result<CompilationOutput> {
+    compile_elements(&html::parse(input), options)
+}
+
+/// Compiles the elements of a page to SWB, see [`CompileOptions`]
+pub fn compile_elements(input: &[Element], options: &CompileOptions) -> Result<CompilationOutput> {
+    let mut compiler = Compiler {
+        images: ImageTable::new(options.image_dir.clone(), options.image_max_width),
+        ..Default::default()
+    };
+    let mut rest = input;
+    while let Some(element) = rest.first() {
+        if let (Element::Tag(Tag { kind: TagKind::Table, .. }), Some(width)) = (element, options.table_text_width) {
+            if let Some((lines, len)) = table::render_text(rest, width) {
+                compiler.lines(&lines);
+                rest = &rest[len..];
+                continue;
+            }
+        }
+        compiler.element(element);
+        rest = &rest[1..];
+    }
+    compiler.meta.timestamp = options.timestamp;
+    Ok(compiler.finish())
 }
 
 impl Display for CompilationOutput {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swb_shared::{Address, AddressRange, FormMethod, Link, SelectOption};
+
+    fn text(base: u32, range: u32) -> Instruction {
+        Instruction::Text(AddressRange {
+            base: Address(base),
+            range,
+        })
+    }
+
+    #[test]
+    fn test_align_from_style() {
+        assert_eq!(align_from_style("text-align: center"), Some(Align::Center));
+        assert_eq!(
+            align_from_style("color:red;TEXT-ALIGN:right !important;"),
+            Some(Align::Right)
+        );
+        assert_eq!(
+            align_from_style("text-align: left; text-align: justify"),
+            Some(Align::Justify)
+        );
+        assert_eq!(align_from_style("text-align: inherit"), None);
+        assert_eq!(align_from_style("margin: 0 auto"), None);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let paragraph = Tag::new(TagKind::Paragraph)
+            .with_id(Some(String::from("intro")))
+            .with_align(Some(String::from("right")))
+            .with_style(Some(String::from("text-align: center")));
+        let input = vec![
+            Element::Tag(paragraph),
+            Element::Text(String::from("Hi")),
+            Element::Tag(Tag::new(TagKind::HorizontalRule).with_align(Some(String::from("left")))),
+            Element::EndTag(TagKind::Paragraph),
+        ];
+        let output = compile_elements(&input, &CompileOptions::default()).unwrap();
+        assert_eq!(output.0.anchors[0].instruction(), Some(0));
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::Anchor(0),
+                Instruction::PushAlign(Align::Center),
+                Instruction::ParagraphStart,
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 2,
+                }),
+                Instruction::Rule(Rule::default()),
+                Instruction::ParagraphEnd,
+                Instruction::PopAlign(Align::Center),
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let source = "<html lang=en-GB><head><title> Home </title>\
+            <meta property=og:title content=Other><meta property=og:url content=https://example.com>\
+            <meta http-equiv=Content-Language content=nl></head><body>Hi</body></html>";
+        let output = compile(source).unwrap();
+        assert_eq!(output.0.meta.title.as_deref(), Some("Home"));
+        assert_eq!(output.0.meta.url.as_deref(), Some("https://example.com"));
+        assert_eq!(output.0.meta.lang.as_deref(), Some("en-GB"));
+        // The title only goes into the metadata
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 2,
+                }),
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        let output = compile("<a href=/about>About</a> <a name=top>Top</a>").unwrap();
+        assert_eq!(output.0.links, vec![Link::Url(String::from("/about"))]);
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::LinkStart(0),
+                text(0, 5),
+                Instruction::LinkEnd,
+                text(5, 3),
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_headings() {
+        let output = compile("<h2>Title</h2>").unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::PushHeading(2),
+                text(0, 5),
+                Instruction::PopHeading(2),
+                Instruction::Stop,
+            ]
+        );
+
+        let input = vec![
+            Element::Tag(Tag::new(TagKind::Heading(9))),
+            Element::EndTag(TagKind::Heading(9)),
+            Element::Tag(Tag::new(TagKind::Heading(0))),
+            Element::EndTag(TagKind::Heading(0)),
+        ];
+        let output = compile_elements(&input, &CompileOptions::default()).unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::PushHeading(6),
+                Instruction::PopHeading(6),
+                Instruction::PushHeading(1),
+                Instruction::PopHeading(1),
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        let output = compile("<ol start=3><li>One<li>Two</ol><ul><li>Dot</ul>").unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::ListStart(ListKind::Ordered(3)),
+                Instruction::ListItem,
+                text(0, 3),
+                Instruction::ListItem,
+                text(3, 3),
+                Instruction::ListEnd,
+                Instruction::ListStart(ListKind::Unordered),
+                Instruction::ListItem,
+                text(6, 3),
+                Instruction::ListEnd,
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tables() {
+        let source = "<table><tr><th>Name<th>Age<tr><td>Alice<td>30</table>";
+        let output = compile(source).unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::TableStart,
+                Instruction::TableRow,
+                Instruction::TableHeaderCell,
+                text(0, 4),
+                Instruction::TableHeaderCell,
+                text(4, 3),
+                Instruction::TableRow,
+                Instruction::TableCell,
+                text(7, 5),
+                Instruction::TableCell,
+                text(12, 2),
+                Instruction::TableEnd,
+                Instruction::Stop,
+            ]
+        );
+
+        let options = CompileOptions::new().with_table_text_width(Some(40));
+        let output = compile_with(source, &options).unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                text(0, 11),
+                Instruction::Endl,
+                text(11, 11),
+                Instruction::Endl,
+                text(22, 10),
+                Instruction::Endl,
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_images() {
+        let dir = std::env::temp_dir().join("swb-compiler-test-images");
+        std::fs::create_dir_all(&dir).unwrap();
+        image::GrayImage::new(2, 2).save(dir.join("dot.png")).unwrap();
+
+        let source = "<img src=dot.png alt=Dot><img src=missing.png alt=\" Missing \">";
+        let options = CompileOptions::new().with_image_dir(Some(dir));
+        let output = compile_with(source, &options).unwrap();
+        assert_eq!(output.0.images.len(), 1);
+        assert_eq!(output.1.skipped_images.len(), 1);
+        assert!(output.1.skipped_images[0].starts_with("missing.png: "));
+        assert_eq!(
+            output.0.code,
+            vec![Instruction::Image(0), text(0, 7), Instruction::Stop]
+        );
+
+        // Without an image directory, only the alt text is used
+        let output = compile(source).unwrap();
+        assert!(output.0.images.is_empty());
+        assert!(output.1.skipped_images.is_empty());
+        assert_eq!(output.0.code, vec![text(0, 3), text(3, 7), Instruction::Stop]);
+    }
+
+    #[test]
+    fn test_forms() {
+        let source = "<form action=/search method=get><input name=q value=rust>\
+            <input type=hidden name=page value=1><input type=checkbox name=safe checked>\
+            <select name=lang><option value=en>English<option selected>Dutch</select>\
+            <button>Go</button></form>";
+        let output = compile(source).unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::TextInput(0),
+                Instruction::Checkbox(2),
+                Instruction::Select(3),
+                Instruction::Submit(4),
+                Instruction::Stop,
+            ]
+        );
+        let forms = &output.0.forms;
+        assert_eq!(forms.forms[0].method, FormMethod::Get);
+        assert_eq!(forms.forms[0].action, "/search");
+        let controls: Vec<_> = forms
+            .controls
+            .iter()
+            .map(|control| (control.kind, control.name.as_str(), control.value.as_str(), control.checked))
+            .collect();
+        assert_eq!(
+            controls,
+            vec![
+                (ControlKind::TextInput, "q", "rust", false),
+                (ControlKind::Hidden, "page", "1", false),
+                (ControlKind::Checkbox, "safe", "on", true),
+                (ControlKind::Select, "lang", "", false),
+                (ControlKind::Submit, "", "Go", false),
+            ]
+        );
+        assert_eq!(
+            forms.controls[3].options,
+            vec![
+                SelectOption {
+                    value: String::from("en"),
+                    label: String::from("English"),
+                    selected: false,
+                },
+                SelectOption {
+                    value: String::from("Dutch"),
+                    label: String::from("Dutch"),
+                    selected: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ids() {
+        let output = compile("<span id=intro>Hi</span><a href=#intro>Up</a><b id=\"\">!</b>").unwrap();
+        assert_eq!(output.0.links, vec![Link::Jump(0)]);
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::Anchor(0),
+                text(0, 2),
+                Instruction::LinkStart(0),
+                text(2, 2),
+                Instruction::LinkEnd,
+                Instruction::Push(StyleVar::Bold),
+                text(4, 1),
+                Instruction::Pop(StyleVar::Bold),
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_styles() {
+        let output = compile("<u>a</u><code>b</code><del>c</del><sup>d</sup><small>e</small>").unwrap();
+        let styles = [
+            StyleVar::Underline,
+            StyleVar::Monospace,
+            StyleVar::Strikethrough,
+            StyleVar::Superscript,
+            StyleVar::Small,
+        ];
+        let expected: Vec<_> = styles
+            .iter()
+            .enumerate()
+            .flat_map(|(i, style)| [Instruction::Push(*style), text(i as u32, 1), Instruction::Pop(*style)])
+            .chain([Instruction::Stop])
+            .collect();
+        assert_eq!(output.0.code, expected);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let output = compile("<div><p>a</p><blockquote>b<blockquote>c</blockquote></blockquote></div>").unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::BlockBreak,
+                Instruction::ParagraphStart,
+                text(0, 1),
+                Instruction::ParagraphEnd,
+                Instruction::Indent(1),
+                text(1, 1),
+                Instruction::Indent(2),
+                text(2, 1),
+                Instruction::Indent(1),
+                Instruction::Indent(0),
+                Instruction::BlockBreak,
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let output = compile("a<hr>b").unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                text(0, 1),
+                Instruction::Rule(Rule::default()),
+                text(1, 1),
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alignment() {
+        let output = compile(
+            "<p align=right>a</p><center>b</center>\
+            <div style='text-align: justify' align=center>c</div>",
+        )
+        .unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::PushAlign(Align::Right),
+                Instruction::ParagraphStart,
+                text(0, 1),
+                Instruction::ParagraphEnd,
+                Instruction::PopAlign(Align::Right),
+                Instruction::PushAlign(Align::Center),
+                Instruction::BlockBreak,
+                text(1, 1),
+                Instruction::BlockBreak,
+                Instruction::PopAlign(Align::Center),
+                Instruction::PushAlign(Align::Justify),
+                Instruction::BlockBreak,
+                text(2, 1),
+                Instruction::BlockBreak,
+                Instruction::PopAlign(Align::Justify),
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preformatted() {
+        // The parser drops the newline right after <pre>, like browsers do
+        let output = compile("<pre>\n\n  a  b\n</pre> c   d ").unwrap();
+        assert_eq!(
+            output.0.code,
+            vec![
+                Instruction::PreformattedStart,
+                Instruction::Push(StyleVar::Monospace),
+                Instruction::Endl,
+                text(0, 6),
+                Instruction::Endl,
+                Instruction::Pop(StyleVar::Monospace),
+                Instruction::PreformattedEnd,
+                text(6, 5),
+                Instruction::Stop,
+            ]
+        );
+    }
+}
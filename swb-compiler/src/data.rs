@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use swb_shared::{Address, AddressRange, Text};
+
+/// Only strings up to this length are looked for inside earlier text, longer ones are only
+/// reused when they are identical to an earlier string.
+const MAX_CONTAINED_LEN: usize = 32;
+
+/// How far back in the data section short strings are looked for, which keeps interning linear
+/// in the size of the page.
+const CONTAINED_WINDOW: usize = 4096;
+
+/// Builds the data section of a program. Identical strings (navigation labels, "Read more", ...)
+/// are stored once, and short strings that already occur inside recent text point into that text.
+#[derive(Debug, Default)]
+pub struct DataSection {
+    text: String,
+    interned: HashMap<String, AddressRange>,
+    saved: usize,
+}
+
+impl DataSection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the range of `data` in the data section, appending it only if it isn't there yet.
+    pub fn intern(&mut self, data: &str) -> AddressRange {
+        if let Some(range) = self.interned.get(data) {
+            self.saved += data.len();
+            return *range;
+        }
+        let start = match self.find_contained(data) {
+            Some(start) => {
+                self.saved += data.len();
+                start
+            }
+            None => {
+                let start = self.text.len();
+                self.text.push_str(data);
+                start
+            }
+        };
+        let range = AddressRange {
+            base: Address(start as u32),
+            range: data.len() as u32,
+        };
+        self.interned.insert(data.to_string(), range);
+        range
+    }
+
+    /// Looks for a short string inside the end of the data section.
+    fn find_contained(&self, data: &str) -> Option<usize> {
+        if data.len() > MAX_CONTAINED_LEN {
+            return None;
+        }
+        let mut window = self.text.len().saturating_sub(CONTAINED_WINDOW);
+        while !self.text.is_char_boundary(window) {
+            window += 1;
+        }
+        self.text[window..].find(data).map(|start| window + start)
+    }
+
+    /// Number of bytes that did not have to be stored because they were shared.
+    pub fn bytes_saved(&self) -> usize {
+        self.saved
+    }
+
+    pub fn into_text(self) -> Text {
+        // This only becomes UTF-8 if the page actually contains non-ascii text
+        Text::from(self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut data = DataSection::new();
+        let first = data.intern("Read more");
+        data.intern("Share");
+        assert_eq!(data.intern("Read more"), first);
+        let more = data.intern("more");
+        assert_eq!(more.base, Address(5));
+        assert_eq!(data.bytes_saved(), "Read more".len() + "more".len());
+        assert_eq!(data.into_text().as_str(), "Read moreShare");
+
+        // Long strings are only reused when they are identical
+        let mut data = DataSection::new();
+        let long = "a".repeat(MAX_CONTAINED_LEN + 1);
+        data.intern(&format!("{long}!"));
+        assert_eq!(data.intern(&long).base, Address(long.len() as u32 + 1));
+        // and short strings only when they are in recent text
+        let mut data = DataSection::new();
+        data.intern("needle");
+        data.intern(&"é".repeat(CONTAINED_WINDOW));
+        assert_eq!(data.intern("needle").base, Address(0));
+        assert_eq!(data.intern("need").base, Address(2 * CONTAINED_WINDOW as u32 + 6));
+    }
+}
@@ -0,0 +1,192 @@
+use swb_shared::{Control, ControlKind, Form, FormMethod, Forms, Instruction, SelectOption};
+
+/// The kind of control an `<input>` of the given type is, or `None` for types that can't be
+/// used on a reader, like file uploads.
+pub fn input_kind(ty: Option<&str>) -> Option<ControlKind> {
+    match ty.map(str::to_ascii_lowercase).as_deref() {
+        Some("checkbox") => Some(ControlKind::Checkbox),
+        Some("radio") => Some(ControlKind::Radio),
+        Some("submit") => Some(ControlKind::Submit),
+        Some("hidden") => Some(ControlKind::Hidden),
+        Some("button" | "reset" | "image" | "file") => None,
+        // Everything else, like email or search, is typed in as text
+        _ => Some(ControlKind::TextInput),
+    }
+}
+
+/// The instruction that shows a control, hidden controls have none.
+pub fn control_instruction(kind: ControlKind, index: u32) -> Option<Instruction> {
+    match kind {
+        ControlKind::TextInput => Some(Instruction::TextInput(index)),
+        ControlKind::Checkbox => Some(Instruction::Checkbox(index)),
+        ControlKind::Radio => Some(Instruction::Radio(index)),
+        ControlKind::Select => Some(Instruction::Select(index)),
+        ControlKind::Submit => Some(Instruction::Submit(index)),
+        ControlKind::Hidden => None,
+    }
+}
+
+/// Builds the form table of a program. Controls outside of a form can't be submitted, so
+/// they're left out.
+#[derive(Debug, Default)]
+pub struct FormTable {
+    forms: Forms,
+    open_form: Option<u32>,
+    // Control that the text inside an element goes to, like the label of a button
+    capture: Option<u32>,
+    // Value of the captured control if there's no text inside it
+    capture_default: String,
+    // Whether the last option of the captured select uses its label as value
+    option_value_from_label: bool,
+}
+
+impl FormTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start_form(&mut self, action: Option<&str>, method: Option<&str>) {
+        let method = match method {
+            Some(method) if method.eq_ignore_ascii_case("post") => FormMethod::Post,
+            _ => FormMethod::Get,
+        };
+        self.open_form = Some(self.forms.forms.len() as u32);
+        self.forms.forms.push(Form {
+            method,
+            // Without an action, forms are submitted to the page itself
+            action: action.unwrap_or("").to_string(),
+        });
+    }
+
+    pub fn end_form(&mut self) {
+        self.open_form = None;
+    }
+
+    /// Adds a control to the open form, returning its index.
+    pub fn add(
+        &mut self,
+        kind: ControlKind,
+        name: Option<&str>,
+        value: &str,
+        checked: bool,
+    ) -> Option<u32> {
+        let form = self.open_form?;
+        self.forms.controls.push(Control {
+            kind,
+            form,
+            name: name.unwrap_or("").to_string(),
+            value: value.to_string(),
+            checked,
+            options: vec![],
+        });
+        Some(self.forms.controls.len() as u32 - 1)
+    }
+
+    /// Sends the text that follows to a control until [`FormTable::end_capture`]: the value of
+    /// buttons and text areas, or the labels of the options of a select. Without text, the
+    /// value becomes `default`.
+    pub fn capture(&mut self, index: u32, default: Option<&str>) {
+        self.capture = Some(index);
+        self.capture_default = default.unwrap_or("").to_string();
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// Adds an option to the captured select, the text that follows is its label.
+    pub fn add_option(&mut self, value: Option<&str>, selected: bool) {
+        self.finish_option();
+        let Some(index) = self.capture else { return };
+        self.option_value_from_label = value.is_none();
+        self.forms.controls[index as usize]
+            .options
+            .push(SelectOption {
+                value: value.unwrap_or("").to_string(),
+                label: String::new(),
+                selected,
+            });
+    }
+
+    pub fn text(&mut self, text: &str) {
+        let Some(index) = self.capture else { return };
+        let control = &mut self.forms.controls[index as usize];
+        let target = match control.kind {
+            ControlKind::Select => match control.options.last_mut() {
+                Some(option) => &mut option.label,
+                // Text before the first option is dropped
+                None => return,
+            },
+            _ => &mut control.value,
+        };
+        if !target.is_empty() {
+            target.push(' ');
+        }
+        target.push_str(text.trim());
+    }
+
+    pub fn end_capture(&mut self) {
+        self.finish_option();
+        if let Some(index) = self.capture.take() {
+            let control = &mut self.forms.controls[index as usize];
+            control.value = control.value.trim().to_string();
+            if control.value.is_empty() {
+                control.value = std::mem::take(&mut self.capture_default);
+            }
+        }
+    }
+
+    fn finish_option(&mut self) {
+        let option = self
+            .capture
+            .and_then(|index| self.forms.controls[index as usize].options.last_mut());
+        if let Some(option) = option {
+            option.label = option.label.trim().to_string();
+            if self.option_value_from_label {
+                option.value = option.label.clone();
+            }
+        }
+        self.option_value_from_label = false;
+    }
+
+    pub fn into_forms(self) -> Forms {
+        self.forms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let mut table = FormTable::new();
+        assert_eq!(
+            table.add(ControlKind::TextInput, Some("q"), "", false),
+            None
+        );
+        table.start_form(Some("/search"), Some("POST"));
+        let index = table
+            .add(ControlKind::Select, Some("lang"), "", false)
+            .unwrap();
+        table.capture(index, None);
+        table.add_option(None, false);
+        table.text(" English ");
+        table.add_option(Some("nl"), true);
+        table.text("Dutch");
+        table.end_capture();
+        table.end_form();
+
+        let forms = table.into_forms();
+        assert_eq!(forms.forms[0].method, FormMethod::Post);
+        let options = &forms.controls[0].options;
+        assert_eq!(
+            (options[0].value.as_str(), options[0].label.as_str()),
+            ("English", "English")
+        );
+        assert_eq!(
+            (options[1].value.as_str(), options[1].label.as_str()),
+            ("nl", "Dutch")
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use kuchiki::traits::TendrilSink;
+use kuchiki::{Attributes, NodeRef};
+
+/// An element of a page, in document order. Every start tag that isn't
+/// [void](TagKind::is_void) is closed by an end tag of the same kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Element {
+    Tag(Tag),
+    EndTag(TagKind),
+    Text(String),
+    LineBreak,
+}
+
+/// A start tag, with the attributes that matter on any kind of element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub kind: TagKind,
+    /// Name that fragment links like `#intro` jump to
+    pub id: Option<String>,
+    /// The `align` attribute
+    pub align: Option<String>,
+    /// The `style` attribute, of which only `text-align` is used
+    pub style: Option<String>,
+}
+
+impl Tag {
+    pub fn new(kind: TagKind) -> Self {
+        Self {
+            kind,
+            id: None,
+            align: None,
+            style: None,
+        }
+    }
+
+    pub fn with_id(self, id: Option<String>) -> Self {
+        Self { id, ..self }
+    }
+
+    pub fn with_align(self, align: Option<String>) -> Self {
+        Self { align, ..self }
+    }
+
+    pub fn with_style(self, style: Option<String>) -> Self {
+        Self { style, ..self }
+    }
+}
+
+impl From<TagKind> for Tag {
+    fn from(kind: TagKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagKind {
+    Bold,
+    Italic,
+    Underline,
+    Code,
+    Strikethrough,
+    Deleted,
+    Superscript,
+    Subscript,
+    Small,
+    Big,
+    Title,
+    Html { lang: Option<String> },
+    /// A `<meta>` tag, `name` is its name or property
+    Meta { name: String, content: String },
+    Anchor { href: Option<String> },
+    Heading(u8),
+    UnorderedList,
+    OrderedList { start: Option<u32> },
+    ListItem,
+    Table,
+    TableRow,
+    TableData,
+    TableHeader,
+    Image { src: Option<String>, alt: Option<String> },
+    Form { action: Option<String>, method: Option<String> },
+    Input {
+        kind: Option<String>,
+        name: Option<String>,
+        value: Option<String>,
+        checked: bool,
+    },
+    Button {
+        kind: Option<String>,
+        name: Option<String>,
+        value: Option<String>,
+    },
+    TextArea { name: Option<String> },
+    Select { name: Option<String> },
+    SelectOption { value: Option<String>, selected: bool },
+    Paragraph,
+    Div,
+    Section,
+    BlockQuote,
+    HorizontalRule,
+    Preformatted,
+    Center,
+    /// Any other element, which only matters for its attributes and content
+    Other,
+}
+
+impl TagKind {
+    /// Whether elements of this kind can't have content, so they have no end tag.
+    pub fn is_void(&self) -> bool {
+        matches!(
+            self,
+            TagKind::Meta { .. } | TagKind::Image { .. } | TagKind::Input { .. } | TagKind::HorizontalRule
+        )
+    }
+}
+
+/// Returns the kind of an element, given its name and attributes
+fn tag_kind(name: &str, attributes: &Attributes) -> TagKind {
+    let attribute = |name: &str| attributes.get(name).map(str::to_string);
+    match name {
+        "b" | "strong" => TagKind::Bold,
+        "i" | "em" => TagKind::Italic,
+        "u" | "ins" => TagKind::Underline,
+        "code" | "kbd" | "samp" | "tt" => TagKind::Code,
+        "s" | "strike" => TagKind::Strikethrough,
+        "del" => TagKind::Deleted,
+        "sup" => TagKind::Superscript,
+        "sub" => TagKind::Subscript,
+        "small" => TagKind::Small,
+        "big" => TagKind::Big,
+        "title" => TagKind::Title,
+        "html" => TagKind::Html { lang: attribute("lang") },
+        "a" => TagKind::Anchor { href: attribute("href") },
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => TagKind::Heading(name.as_bytes()[1] - b'0'),
+        "ul" => TagKind::UnorderedList,
+        "ol" => TagKind::OrderedList {
+            start: attribute("start").and_then(|start| start.trim().parse().ok()),
+        },
+        "li" => TagKind::ListItem,
+        "table" => TagKind::Table,
+        "tr" => TagKind::TableRow,
+        "td" => TagKind::TableData,
+        "th" => TagKind::TableHeader,
+        "img" => TagKind::Image {
+            src: attribute("src"),
+            alt: attribute("alt"),
+        },
+        "form" => TagKind::Form {
+            action: attribute("action"),
+            method: attribute("method"),
+        },
+        "input" => TagKind::Input {
+            kind: attribute("type"),
+            name: attribute("name"),
+            value: attribute("value"),
+            checked: attributes.contains("checked"),
+        },
+        "button" => TagKind::Button {
+            kind: attribute("type"),
+            name: attribute("name"),
+            value: attribute("value"),
+        },
+        "textarea" => TagKind::TextArea { name: attribute("name") },
+        "select" => TagKind::Select { name: attribute("name") },
+        "option" => TagKind::SelectOption {
+            value: attribute("value"),
+            selected: attributes.contains("selected"),
+        },
+        "p" => TagKind::Paragraph,
+        "div" => TagKind::Div,
+        // Like <section>, these only group other blocks
+        "section" | "article" | "main" | "header" | "footer" | "nav" | "aside" => TagKind::Section,
+        "blockquote" => TagKind::BlockQuote,
+        "center" => TagKind::Center,
+        "hr" => TagKind::HorizontalRule,
+        "pre" => TagKind::Preformatted,
+        "meta" => {
+            // Open Graph tags use property instead of name
+            let name = attribute("name")
+                .or_else(|| attribute("property"))
+                .or_else(|| attribute("http-equiv"));
+            match (name, attribute("content")) {
+                (Some(name), Some(content)) => TagKind::Meta {
+                    name: name.to_ascii_lowercase(),
+                    content,
+                },
+                _ => TagKind::Other,
+            }
+        }
+        _ => TagKind::Other,
+    }
+}
+
+/// Collapses every run of whitespace into one space, like browsers do
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            collapsed.push(c);
+        } else if !collapsed.ends_with(' ') {
+            collapsed.push(' ');
+        }
+    }
+    collapsed
+}
+
+/// Adds the elements of a node and everything inside of it. Text inside a `<pre>` keeps its
+/// whitespace.
+fn walk(node: &NodeRef, preformatted: bool, elements: &mut Vec<Element>) {
+    if let Some(text) = node.as_text() {
+        let text = text.borrow();
+        if preformatted {
+            elements.push(Element::Text(text.clone()));
+        } else if !text.trim().is_empty() {
+            elements.push(Element::Text(collapse_whitespace(&text)));
+        }
+        return;
+    }
+    let Some(element) = node.as_element() else {
+        // The document itself, comments and doctypes only matter for what they contain
+        for child in node.children() {
+            walk(&child, preformatted, elements);
+        }
+        return;
+    };
+    let name = &*element.name.local;
+    let attributes = element.attributes.borrow();
+    let kind = match name {
+        "script" | "style" | "template" => return,
+        "br" => {
+            elements.push(Element::LineBreak);
+            return;
+        }
+        name => tag_kind(name, &attributes),
+    };
+    let attribute = |name: &str| {
+        attributes
+            .get(name)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let tag = Tag::new(kind.clone())
+        .with_id(attribute("id"))
+        .with_align(attribute("align"))
+        .with_style(attribute("style"));
+    elements.push(Element::Tag(tag));
+    if !kind.is_void() {
+        let preformatted = preformatted || kind == TagKind::Preformatted;
+        for child in node.children() {
+            walk(&child, preformatted, elements);
+        }
+        elements.push(Element::EndTag(kind));
+    }
+}
+
+/// Parses an HTML page into its elements. Scripts, styles and comments are left out. Outside of
+/// `<pre>`, runs of whitespace in text are collapsed into one space, and text that is only
+/// whitespace is left out.
+pub fn parse(source: &str) -> Vec<Element> {
+    let document = kuchiki::parse_html().one(source);
+    let mut elements = vec![];
+    walk(&document, false, &mut elements);
+    elements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let source = "<!DOCTYPE html><html lang=nl><head><title>Hi</title>\
+            <meta property=og:url content=https://example.com><script>let x;</script></head>\
+            <body><!-- note --><b>Hello,\n   world</b><br> </body></html>";
+        assert_eq!(
+            parse(source),
+            vec![
+                Element::Tag(Tag::new(TagKind::Html {
+                    lang: Some(String::from("nl"))
+                })),
+                Element::Tag(Tag::new(TagKind::Other)),
+                Element::Tag(Tag::new(TagKind::Title)),
+                Element::Text(String::from("Hi")),
+                Element::EndTag(TagKind::Title),
+                Element::Tag(Tag::new(TagKind::Meta {
+                    name: String::from("og:url"),
+                    content: String::from("https://example.com"),
+                })),
+                Element::EndTag(TagKind::Other),
+                Element::Tag(Tag::new(TagKind::Other)),
+                Element::Tag(Tag::new(TagKind::Bold)),
+                Element::Text(String::from("Hello, world")),
+                Element::EndTag(TagKind::Bold),
+                Element::LineBreak,
+                Element::EndTag(TagKind::Other),
+                Element::EndTag(TagKind::Html {
+                    lang: Some(String::from("nl"))
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::{anyhow, Result};
+use image::imageops::{self, FilterType};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use swb_shared::{Bitmap, Image};
+
+/// Pixels with a luma below this become black
+const THRESHOLD: i16 = 128;
+
+/// Converts grayscale pixels with alpha, stored row by row, to a 1-bit bitmap using
+/// Floyd–Steinberg dithering. Transparent pixels are drawn on a white background.
+pub fn dither(width: u16, height: u16, pixels: &[[u8; 2]]) -> Bitmap {
+    let (w, h) = (width as usize, height as usize);
+    assert_eq!(pixels.len(), w * h);
+    let mut luma: Vec<i16> = pixels
+        .iter()
+        .map(|[value, alpha]| {
+            let (value, alpha) = (*value as u32, *alpha as u32);
+            ((value * alpha + 255 * (255 - alpha)) / 255) as i16
+        })
+        .collect();
+
+    let mut bitmap = Bitmap::new(width, height);
+    for y in 0..h {
+        for x in 0..w {
+            let old = luma[y * w + x];
+            let black = old < THRESHOLD;
+            bitmap.set_pixel(x as u16, y as u16, black);
+            // Spread the difference between the pixel and what was drawn over its neighbours
+            let error = old - if black { 0 } else { 255 };
+            let mut spread = |dx: isize, dy: usize, weight: i16| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < w && y + dy < h {
+                    luma[(y + dy) * w + nx as usize] += error * weight / 16;
+                }
+            };
+            spread(1, 0, 7);
+            spread(-1, 1, 3);
+            spread(0, 1, 5);
+            spread(1, 1, 1);
+        }
+    }
+    bitmap
+}
+
+/// Loads an image file and dithers it, scaling it down first if it's wider than `max_width`.
+pub fn load(path: &Path, max_width: Option<u32>) -> Result<Bitmap> {
+    let mut image = image::open(path)?.to_luma_alpha8();
+    if let Some(max_width) = max_width.filter(|max_width| image.width() > *max_width) {
+        // Keep the aspect ratio, but don't scale anything down to nothing
+        let height =
+            (image.height() as u64 * max_width as u64 / image.width() as u64).max(1) as u32;
+        image = imageops::resize(&image, max_width, height, FilterType::Triangle);
+    }
+    let (width, height) = (image.width(), image.height());
+    let size = |value: u32| {
+        u16::try_from(value).map_err(|_| anyhow!("image of {width}x{height} is too large"))
+    };
+    let pixels: Vec<[u8; 2]> = image.pixels().map(|pixel| pixel.0).collect();
+    Ok(dither(size(width)?, size(height)?, &pixels))
+}
+
+/// Resolves the `src` of an image to a file below `dir`.
+fn path(dir: &Path, src: &str) -> Option<PathBuf> {
+    // Anything with a scheme, like http: or data:, isn't a local file
+    if src.split('/').next()?.contains(':') {
+        return None;
+    }
+    let src = src.split(['?', '#']).next()?;
+    let src = percent_decode(src)?;
+    let src = Path::new(&src);
+    if !src
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(dir.join(src))
+}
+
+/// Decodes `%` escapes in a URL path, like `%20` for a space.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Builds the image table of a program from local files. Images with the same `src` share
+/// one entry.
+#[derive(Debug, Default)]
+pub struct ImageTable {
+    images: Vec<Image>,
+    // Also remembers sources that failed to load, so they're only tried once
+    indices: HashMap<String, Option<u32>>,
+    skipped: Vec<String>,
+    dir: Option<PathBuf>,
+    max_width: Option<u32>,
+}
+
+impl ImageTable {
+    /// Images are looked up relative to `dir`, without a directory no images are loaded.
+    pub fn new(dir: Option<PathBuf>, max_width: Option<u32>) -> Self {
+        Self {
+            dir,
+            max_width,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the index of the image at `src`, loading it if it isn't there yet, or `None`
+    /// if it can't be loaded. Only relative paths below the image directory are loaded, not
+    /// URLs or paths leading out of the directory. Any query or fragment is ignored.
+    pub fn intern(&mut self, src: &str) -> Option<u32> {
+        if let Some(index) = self.indices.get(src) {
+            return *index;
+        }
+        let loaded = self.dir.as_ref().map(|dir| {
+            let path = path(dir, src).ok_or_else(|| anyhow!("not a relative path"))?;
+            load(&path, self.max_width)
+        });
+        let index = match loaded {
+            Some(Ok(bitmap)) => {
+                self.images.push(Image::Mono(bitmap));
+                Some(self.images.len() as u32 - 1)
+            }
+            Some(Err(e)) => {
+                self.skipped.push(format!("{src}: {e}"));
+                None
+            }
+            // Loading images is turned off
+            None => None,
+        };
+        self.indices.insert(src.to_string(), index);
+        index
+    }
+
+    /// The sources of images that couldn't be loaded, each followed by the reason.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    pub fn into_images(self) -> Vec<Image> {
+        self.images
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dither() {
+        // Solid colours stay solid, and transparency is white
+        let bitmap = dither(2, 2, &[[0, 255], [255, 255], [0, 0], [0, 255]]);
+        let view = bitmap.view();
+        assert!(view.pixel(0, 0) && !view.pixel(1, 0) && !view.pixel(0, 1) && view.pixel(1, 1));
+
+        // Mid gray comes out as roughly half black
+        let bitmap = dither(16, 16, &[[128, 255]; 256]);
+        let black: u32 = bitmap.rows.iter().map(|byte| byte.count_ones()).sum();
+        assert!((96..=160).contains(&black), "{black} black pixels");
+
+        let mut table = ImageTable::new(Some(PathBuf::from(".")), None);
+        assert_eq!(table.intern("https://example.com/a.png"), None);
+        assert_eq!(table.intern("../a.png"), None);
+        assert_eq!(table.intern("missing.png"), None);
+        assert_eq!(table.skipped().len(), 3);
+        assert!(table.into_images().is_empty());
+
+        let dir = Path::new("images");
+        assert_eq!(path(dir, "a%20b.png?v=2#top"), Some(dir.join("a b.png")));
+        assert_eq!(path(dir, "%2e%2e/a.png"), None);
+        assert_eq!(path(dir, "a%2.png"), None);
+    }
+}
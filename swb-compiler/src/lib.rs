@@ -1,4 +1,16 @@
+pub mod anchors;
 pub mod compiler;
+pub mod data;
+pub mod forms;
+pub mod html;
+pub mod images;
+pub mod links;
+pub mod table;
 
 pub use compiler::compile;
-pub use compiler::CompilationOutput;
\ No newline at end of file
+pub use compiler::compile_with;
+pub use compiler::compile_elements;
+pub use compiler::CompileOptions;
+pub use compiler::CompilationOutput;
+pub use compiler::CompilationStats;
+pub use swb_shared::{CodeEncoding, TextCompression};
\ No newline at end of file
@@ -0,0 +1,73 @@
+use crate::anchors::AnchorTable;
+use std::collections::HashMap;
+use swb_shared::Link;
+
+/// Builds the link table of a program. Links to the same target share one entry.
+#[derive(Debug, Default)]
+pub struct LinkTable {
+    links: Vec<Link>,
+    indices: HashMap<String, u32>,
+}
+
+impl LinkTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the index of the link to `href`, adding it if it isn't there yet.
+    pub fn intern(&mut self, href: &str) -> u32 {
+        if let Some(index) = self.indices.get(href) {
+            return *index;
+        }
+        let index = self.links.len() as u32;
+        self.links.push(Link::Url(href.to_string()));
+        self.indices.insert(href.to_string(), index);
+        index
+    }
+
+    /// Turns links to a fragment of the page itself, like `#intro`, into jumps to the anchor
+    /// with that name. Links to `#` or `#top` without such an anchor jump to the start.
+    pub fn resolve_fragments(&mut self, anchors: &AnchorTable) {
+        for link in &mut self.links {
+            let Link::Url(url) = link else { continue };
+            let Some(name) = url.strip_prefix('#') else { continue };
+            let instruction = match anchors.instruction(name) {
+                Some(instruction) => instruction,
+                None if name.is_empty() || name.eq_ignore_ascii_case("top") => 0,
+                None => continue,
+            };
+            *link = Link::Jump(instruction);
+        }
+    }
+
+    pub fn into_links(self) -> Vec<Link> {
+        self.links
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_fragments() {
+        let mut anchors = AnchorTable::new();
+        assert_eq!(anchors.add("intro", 4), Some(0));
+        assert_eq!(anchors.add("intro", 9), None);
+
+        let mut links = LinkTable::new();
+        for href in ["#intro", "#top", "#missing", "https://example.com/#intro"] {
+            links.intern(href);
+        }
+        links.resolve_fragments(&anchors);
+        assert_eq!(
+            links.into_links(),
+            vec![
+                Link::Jump(4),
+                Link::Jump(0),
+                Link::Url(String::from("#missing")),
+                Link::Url(String::from("https://example.com/#intro")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::html::{Element, Tag, TagKind};
+
+/// Separates columns of a rendered table.
+const COLUMN_SEPARATOR: &str = " | ";
+
+#[derive(Debug, Default)]
+struct Row {
+    cells: Vec<String>,
+    header: bool,
+}
+
+/// Renders the table that `elements` starts with as lines of fixed-width text, for readers
+/// that don't implement table layout. Returns the lines and the number of elements the table
+/// spans, or `None` if the table is wider than `max_width` characters, or contains anything
+/// that would get lost as plain text, like nested tables, links, images, form controls or ids.
+pub fn render_text(elements: &[Element], max_width: usize) -> Option<(Vec<String>, usize)> {
+    let mut rows: Vec<Row> = vec![];
+    let mut len = None;
+    for (i, element) in elements.iter().enumerate() {
+        match element {
+            Element::EndTag(TagKind::Table) => {
+                len = Some(i + 1);
+                break;
+            }
+            // Fragment links to an id need an anchor where it is
+            Element::Tag(Tag { id: Some(_), .. }) => return None,
+            Element::Tag(Tag { kind: TagKind::Table, .. }) if i == 0 => {}
+            Element::Tag(Tag { kind, .. }) => match kind {
+                TagKind::Table
+                | TagKind::Anchor { .. }
+                | TagKind::Image { .. }
+                | TagKind::Form { .. }
+                | TagKind::Input { .. }
+                | TagKind::Button { .. }
+                | TagKind::TextArea { .. }
+                | TagKind::Select { .. }
+                | TagKind::SelectOption { .. } => return None,
+                TagKind::TableRow => rows.push(Row::default()),
+                TagKind::TableData | TagKind::TableHeader => {
+                    if rows.is_empty() {
+                        rows.push(Row::default());
+                    }
+                    let row = rows.last_mut().unwrap();
+                    row.header |= *kind == TagKind::TableHeader;
+                    row.cells.push(String::new());
+                }
+                // Formatting inside of cells is dropped
+                _ => {}
+            },
+            Element::Text(text) => match rows.last_mut().and_then(|row| row.cells.last_mut()) {
+                Some(cell) => {
+                    cell.push(' ');
+                    cell.push_str(text);
+                }
+                // Text outside of cells, like captions, has nowhere to go
+                None if text.trim().is_empty() => {}
+                None => return None,
+            },
+            _ => {}
+        }
+    }
+    let len = len?;
+
+    let rows: Vec<Row> = rows
+        .into_iter()
+        .map(|row| Row {
+            cells: row
+                .cells
+                .iter()
+                .map(|cell| cell.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect(),
+            header: row.header,
+        })
+        .collect();
+    let columns = rows.iter().map(|row| row.cells.len()).max().filter(|columns| *columns > 0)?;
+    let mut widths = vec![0; columns];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(&row.cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let total = widths.iter().sum::<usize>() + COLUMN_SEPARATOR.len() * (columns - 1);
+    if total > max_width {
+        return None;
+    }
+
+    let mut lines = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        let cells = widths
+            .iter()
+            .enumerate()
+            .map(|(column, width)| {
+                let cell = row.cells.get(column).map(String::as_str).unwrap_or("");
+                format!("{cell:width$}")
+            })
+            .collect::<Vec<_>>();
+        lines.push(cells.join(COLUMN_SEPARATOR).trim_end().to_string());
+        if row.header && i + 1 < rows.len() {
+            let rule = widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>();
+            lines.push(rule.join("-+-"));
+        }
+    }
+    Some((lines, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_text() {
+        let text = |text: &str| Element::Text(text.to_string());
+        let tag = |kind: TagKind| Element::Tag(Tag::new(kind));
+        let elements = vec![
+            tag(TagKind::Table),
+            tag(TagKind::TableRow),
+            tag(TagKind::TableHeader),
+            text("Name"),
+            tag(TagKind::TableHeader),
+            text("Age"),
+            tag(TagKind::TableRow),
+            tag(TagKind::TableData),
+            tag(TagKind::Bold),
+            text("Alice"),
+            Element::EndTag(TagKind::Bold),
+            tag(TagKind::TableData),
+            text("30"),
+            Element::EndTag(TagKind::Table),
+            text("after"),
+        ];
+        let (lines, len) = render_text(&elements, 40).unwrap();
+        assert_eq!(lines, vec!["Name  | Age", "------+----", "Alice | 30"]);
+        assert_eq!(len, elements.len() - 1);
+
+        assert!(render_text(&elements, 10).is_none());
+        assert!(render_text(&elements[..5], 40).is_none());
+
+        // Adds an element to Alice's cell
+        let with = |element: Element| {
+            let mut elements = elements.clone();
+            elements.insert(9, element);
+            elements
+        };
+        let image = TagKind::Image {
+            src: Some(String::from("alice.png")),
+            alt: None,
+        };
+        assert!(render_text(&with(tag(image)), 40).is_none());
+        let input = TagKind::Input {
+            kind: None,
+            name: Some(String::from("age")),
+            value: None,
+            checked: false,
+        };
+        assert!(render_text(&with(tag(input)), 40).is_none());
+        let span = Tag::new(TagKind::Other).with_id(Some(String::from("alice")));
+        assert!(render_text(&with(Element::Tag(span)), 40).is_none());
+        let mut elements = elements.clone();
+        elements[0] = Element::Tag(Tag::new(TagKind::Table).with_id(Some(String::from("ages"))));
+        assert!(render_text(&elements, 40).is_none());
+    }
+}
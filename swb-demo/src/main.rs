@@ -134,10 +134,7 @@ async fn main(_spawner: Spawner) {
     toekomst::display::init_disp(Size::new(400, 240));
 
     let input = include_str!("../../examples/example.html");
-    let doc = less_html::Document::from_string(input.to_string()).unwrap();
-    let html = less_html::parse(&doc).unwrap();
-    let stripped = less_html::strip::oracle_strip(html, &strip).unwrap();
-    let swb = swb_compiler::compile(&stripped).unwrap();
+    let swb = swb_compiler::compile(input).unwrap();
 
     select(toekomst::display::run_disp(), ui(&swb)).await;
 
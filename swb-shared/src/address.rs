@@ -26,9 +26,14 @@ impl fmt::Display for Address {
     }
 }
 
+/// Shows the range with its last address included, like `0x0000..0x0004` for 5 bytes. An empty
+/// range has no last address, so it's shown as `0x0000+0`.
 impl fmt::Display for AddressRange {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let end = self.base.offset((self.range - 1) as i32);
+        if self.range == 0 {
+            return write!(f, "{}+0", self.base);
+        }
+        let end = Address(self.base.0.wrapping_add(self.range - 1));
         write!(f, "{}..{}", self.base, end)
     }
 }
\ No newline at end of file
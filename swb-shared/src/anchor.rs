@@ -0,0 +1,161 @@
+#[cfg(not(feature = "std"))]
+use core::str;
+#[cfg(feature = "std")]
+use std::str;
+
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::section::write_record;
+#[cfg(feature = "alloc")]
+use crate::write_varint;
+use crate::section::read_record;
+use crate::{read_varint, ErrorKind, Result};
+
+/// Kinds of entries in an anchor table.
+pub mod kind {
+    /// A named position in the code.
+    pub const NAMED: u8 = 1;
+}
+
+/// An anchor, borrowed from an encoded [`crate::SectionType::ANCHORS`] section.
+///
+/// The section is a list of records, one per anchor in the order of their index. Each record
+/// is a kind byte (see [`kind`]), the LEB128 encoded length of the value and the value itself.
+/// The value of a named anchor is the LEB128 encoded index of the
+/// [`crate::Instruction::Anchor`] instruction, followed by the name as UTF-8.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AnchorView<'a> {
+    /// A named position in the code, like the element with `id="intro"` that `href="#intro"`
+    /// points to.
+    Named { name: &'a str, instruction: u32 },
+    /// A kind of anchor this reader doesn't know, which can't be found by name.
+    Unknown { kind: u8, data: &'a [u8] },
+}
+
+impl<'a> AnchorView<'a> {
+    /// The name of a named anchor.
+    pub fn name(&self) -> Option<&'a str> {
+        match self {
+            AnchorView::Named { name, .. } => Some(name),
+            AnchorView::Unknown { .. } => None,
+        }
+    }
+
+    /// The index of the instruction a named anchor is at.
+    pub fn instruction(&self) -> Option<u32> {
+        match self {
+            AnchorView::Named { instruction, .. } => Some(*instruction),
+            AnchorView::Unknown { .. } => None,
+        }
+    }
+}
+
+/// Iterates over the anchors of an encoded anchor table. Iteration stops after the first
+/// error.
+#[derive(Debug, Clone)]
+pub struct AnchorTable<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> AnchorTable<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Returns the anchor with the given index, as used by [`crate::Instruction::Anchor`].
+    pub fn get(&self, index: u32) -> Option<Result<AnchorView<'a>>> {
+        self.clone().nth(index as usize)
+    }
+
+    /// Finds an anchor by name, errors are returned as if they matched.
+    pub fn find(&self, name: &str) -> Option<Result<AnchorView<'a>>> {
+        Iterator::find(&mut self.clone(), |anchor| match anchor {
+            Ok(anchor) => anchor.name() == Some(name),
+            Err(_) => true,
+        })
+    }
+
+    fn decode_next(&mut self) -> Result<AnchorView<'a>> {
+        let (kind, value, len) = read_record(&self.bytes[self.pos..])?;
+        let invalid = || crate::Error::new(ErrorKind::InvalidAnchor { kind });
+        let anchor = match kind {
+            kind::NAMED => {
+                let (instruction, index_len) = read_varint(value).map_err(|_| invalid())?;
+                let instruction = u32::try_from(instruction).map_err(|_| invalid())?;
+                let name = str::from_utf8(&value[index_len..]).map_err(|_| invalid())?;
+                AnchorView::Named { name, instruction }
+            }
+            kind => AnchorView::Unknown { kind, data: value },
+        };
+        self.pos += len;
+        Ok(anchor)
+    }
+}
+
+impl<'a> Iterator for AnchorTable<'a> {
+    type Item = Result<AnchorView<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.bytes.len() {
+            return None;
+        }
+        let result = self.decode_next().map_err(|e| e.with_offset(self.pos));
+        if result.is_err() {
+            self.pos = self.bytes.len();
+        }
+        Some(result)
+    }
+}
+
+/// Owned version of [`AnchorView`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Anchor {
+    Named { name: String, instruction: u32 },
+    Unknown { kind: u8, data: Vec<u8> },
+}
+
+#[cfg(feature = "alloc")]
+impl Anchor {
+    /// The index of the instruction a named anchor is at.
+    pub fn instruction(&self) -> Option<u32> {
+        match self {
+            Anchor::Named { instruction, .. } => Some(*instruction),
+            Anchor::Unknown { .. } => None,
+        }
+    }
+
+    /// Appends the record for this anchor to an encoded anchor table.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Anchor::Named { name, instruction } => {
+                let mut value = Vec::with_capacity(name.len() + 5);
+                write_varint(*instruction as u64, &mut value);
+                value.extend_from_slice(name.as_bytes());
+                write_record(kind::NAMED, &value, out);
+            }
+            Anchor::Unknown { kind, data } => write_record(*kind, data, out),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<AnchorView<'a>> for Anchor {
+    fn from(view: AnchorView<'a>) -> Self {
+        match view {
+            AnchorView::Named { name, instruction } => Anchor::Named {
+                name: name.to_string(),
+                instruction,
+            },
+            AnchorView::Unknown { kind, data } => Anchor::Unknown {
+                kind,
+                data: data.to_vec(),
+            },
+        }
+    }
+}
@@ -0,0 +1,858 @@
+//! Assembler for the text format written by the `Display` implementation of [`Program`], so
+//! programs can be written by hand.
+//!
+//! A program is a list of sections, each started by a directive like `.text` on a line of its
+//! own. The entries of a section are on the indented lines that follow it. Fields are separated
+//! by whitespace, strings are quoted and escaped like Rust strings, and numbers are decimal or
+//! hexadecimal with a `0x` prefix. Empty lines and lines starting with `;` are skipped.
+//!
+//! Only the syntax is checked. A program can refer to text, links or controls that don't exist,
+//! which [`crate::ProgramView`] rejects once the program is encoded.
+
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+use ascii::AsciiString;
+
+use crate::instruction::{parse_align_value, parse_rule_style, parse_style_var};
+use crate::{
+    Address, AddressRange, Align, Anchor, Bitmap, Control, ControlKind, Form, FormMethod, Image,
+    Instruction, Link, ListKind, Program, Rule, RuleStyle, Section, SectionType, SelectOption,
+    StyleVar, Text,
+};
+
+/// A syntax error in an assembled program.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AsmError {
+    /// Line of the error, starting at 1.
+    pub line: usize,
+    /// Column of the error in characters, starting at 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl error::Error for AsmError {}
+
+type Result<T> = std::result::Result<T, AsmError>;
+
+/// Parses a program in the format written by its `Display` implementation.
+pub fn assemble(source: &str) -> Result<Program> {
+    let mut assembler = Assembler::default();
+    let mut lines = source
+        .split('\n')
+        .enumerate()
+        .map(|(index, line)| Line::new(index + 1, line));
+    while let Some(mut line) = lines.next() {
+        if line.is_blank() {
+            continue;
+        }
+        if line.starts_with('.') {
+            assembler.directive(&mut line)?;
+        } else if line.starts_with_whitespace() {
+            assembler.entry(&mut line, &mut lines)?;
+        } else {
+            return Err(line.error("expected a directive like `.text` or an indented entry"));
+        }
+        line.end()?;
+    }
+    assembler.finish()
+}
+
+impl FromStr for Program {
+    type Err = AsmError;
+
+    fn from_str(source: &str) -> Result<Self> {
+        assemble(source)
+    }
+}
+
+/// The section that entries are added to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Directive {
+    Meta,
+    Data,
+    Text,
+    Links,
+    Anchors,
+    Images,
+    Forms,
+    /// A raw section, the index in [`Program::sections`].
+    Section(usize),
+}
+
+#[derive(Debug, Default)]
+struct Assembler {
+    program: Program,
+    directive: Option<Directive>,
+    seen: Vec<Directive>,
+    text: String,
+    utf8: bool,
+}
+
+impl Assembler {
+    fn directive(&mut self, line: &mut Line) -> Result<()> {
+        let (column, name) = line.word("a directive")?;
+        let directive = match name {
+            ".meta" => Directive::Meta,
+            ".data" => {
+                if let Some((column, flag)) = line.next_word() {
+                    if flag != "utf8" {
+                        return Err(line.error_at(column, format!("unknown data flag `{flag}`")));
+                    }
+                    self.utf8 = true;
+                }
+                Directive::Data
+            }
+            ".text" => Directive::Text,
+            ".links" => Directive::Links,
+            ".anchors" => Directive::Anchors,
+            ".images" => Directive::Images,
+            ".forms" => Directive::Forms,
+            ".section" => {
+                let (column, ty) = line.number::<u32>("a section type")?;
+                let ty = SectionType(ty);
+                if ty.is_known() {
+                    return Err(line.error_at(
+                        column,
+                        format!("section type {:#x} has its own directive", ty.0),
+                    ));
+                }
+                self.program.sections.push(Section { ty, data: vec![] });
+                Directive::Section(self.program.sections.len() - 1)
+            }
+            _ => return Err(line.error_at(column, format!("unknown directive `{name}`"))),
+        };
+        // Only raw sections can appear more than once, with different types
+        if !matches!(directive, Directive::Section(_)) && self.seen.contains(&directive) {
+            return Err(line.error_at(column, format!("duplicate `{name}` directive")));
+        }
+        self.seen.push(directive);
+        self.directive = Some(directive);
+        Ok(())
+    }
+
+    fn entry<'a>(
+        &mut self,
+        line: &mut Line<'a>,
+        lines: &mut impl Iterator<Item = Line<'a>>,
+    ) -> Result<()> {
+        match self.directive {
+            Some(Directive::Meta) => self.meta(line),
+            Some(Directive::Data) => self.data(line),
+            Some(Directive::Text) => {
+                let instruction = instruction(line)?;
+                self.program.code.push(instruction);
+                Ok(())
+            }
+            Some(Directive::Links) => self.link(line),
+            Some(Directive::Anchors) => self.anchor(line),
+            Some(Directive::Images) => self.image(line, lines),
+            Some(Directive::Forms) => self.form(line),
+            Some(Directive::Section(index)) => {
+                line.address(self.program.sections[index].data.len())?;
+                let bytes = line.bytes()?;
+                self.program.sections[index].data.extend(bytes);
+                Ok(())
+            }
+            None => Err(line.error("entry before the first directive")),
+        }
+    }
+
+    fn meta(&mut self, line: &mut Line) -> Result<()> {
+        let (column, key) = line.word("a metadata key")?;
+        let meta = &mut self.program.meta;
+        let duplicate = match key {
+            "timestamp" => meta
+                .timestamp
+                .replace(line.number("a timestamp")?.1)
+                .is_some(),
+            "title" | "url" | "lang" | "compiler" => {
+                let value = line.string()?;
+                let field = match key {
+                    "title" => &mut meta.title,
+                    "url" => &mut meta.url,
+                    "lang" => &mut meta.lang,
+                    _ => &mut meta.compiler,
+                };
+                field.replace(value).is_some()
+            }
+            _ => return Err(line.error_at(column, format!("unknown metadata key `{key}`"))),
+        };
+        if duplicate {
+            return Err(line.error_at(column, format!("duplicate metadata key `{key}`")));
+        }
+        Ok(())
+    }
+
+    fn data(&mut self, line: &mut Line) -> Result<()> {
+        line.address(self.text.len())?;
+        line.peek();
+        let column = line.column();
+        let block = line.string()?;
+        if !self.utf8 && !block.is_ascii() {
+            return Err(line.error_at(column, "non-ascii text without `.data utf8`"));
+        }
+        self.text.push_str(&block);
+        Ok(())
+    }
+
+    fn link(&mut self, line: &mut Line) -> Result<()> {
+        line.index(self.program.links.len())?;
+        let link = if line.peek() == Some('"') {
+            Link::Url(line.string()?)
+        } else if line.peek_word() == Some("jump") {
+            line.word("jump")?;
+            Link::Jump(line.number("an instruction index")?.1)
+        } else {
+            let kind = line.number("a link kind")?.1;
+            Link::Unknown {
+                kind,
+                data: line.bytes()?,
+            }
+        };
+        self.program.links.push(link);
+        Ok(())
+    }
+
+    fn anchor(&mut self, line: &mut Line) -> Result<()> {
+        line.index(self.program.anchors.len())?;
+        let anchor = if line.peek() == Some('"') {
+            let name = line.string()?;
+            let instruction = line.number("an instruction index")?.1;
+            Anchor::Named { name, instruction }
+        } else {
+            let kind = line.number("an anchor kind")?.1;
+            Anchor::Unknown {
+                kind,
+                data: line.bytes()?,
+            }
+        };
+        self.program.anchors.push(anchor);
+        Ok(())
+    }
+
+    fn image<'a>(
+        &mut self,
+        line: &mut Line<'a>,
+        lines: &mut impl Iterator<Item = Line<'a>>,
+    ) -> Result<()> {
+        line.index(self.program.images.len())?;
+        if line.peek_word() != Some("mono") {
+            let format = line.number("an image format")?.1;
+            let data = line.bytes()?;
+            self.program.images.push(Image::Unknown { format, data });
+            return Ok(());
+        }
+        line.word("mono")?;
+        let (column, size) = line.word("a size like `16x8`")?;
+        let (width, height) = size
+            .split_once('x')
+            .and_then(|(width, height)| {
+                Some((width.parse::<u16>().ok()?, height.parse::<u16>().ok()?))
+            })
+            .ok_or_else(|| line.error_at(column, format!("invalid image size `{size}`")))?;
+        line.end()?;
+        // Followed by a line per row, with `#` for black and `.` for white pixels
+        let mut bitmap = Bitmap::new(width, height);
+        for y in 0..height {
+            let mut row = lines
+                .next()
+                .ok_or_else(|| line.error(format!("image ends after {y} of {height} rows")))?;
+            let (column, pixels) = row.rest();
+            if pixels.chars().count() != width as usize {
+                return Err(row.error_at(column, format!("expected a row of {width} pixels")));
+            }
+            for (x, pixel) in pixels.chars().enumerate() {
+                match pixel {
+                    '#' => bitmap.set_pixel(x as u16, y, true),
+                    '.' => {}
+                    _ => return Err(row.error_at(column + x, format!("invalid pixel `{pixel}`"))),
+                }
+            }
+        }
+        self.program.images.push(Image::Mono(bitmap));
+        Ok(())
+    }
+
+    fn form(&mut self, line: &mut Line) -> Result<()> {
+        let (column, kind) = line.word("`form`, `option` or a control kind")?;
+        let forms = &mut self.program.forms;
+        match kind {
+            "form" => {
+                line.index(forms.forms.len())?;
+                let (column, method) = line.word("a form method")?;
+                let method = (1..=u8::MAX)
+                    .map_while(FormMethod::from_byte)
+                    .find(|known| known.as_str() == method)
+                    .ok_or_else(|| {
+                        line.error_at(column, format!("unknown form method `{method}`"))
+                    })?;
+                let action = line.string()?;
+                forms.forms.push(Form { method, action });
+            }
+            "option" => {
+                let control = forms
+                    .controls
+                    .last_mut()
+                    .ok_or_else(|| line.error_at(column, "option before the first control"))?;
+                let value = line.string()?;
+                let label = line.string()?;
+                let selected = line.flag("selected");
+                control.options.push(SelectOption {
+                    value,
+                    label,
+                    selected,
+                });
+            }
+            _ => {
+                let kind = control_kind(kind).ok_or_else(|| {
+                    line.error_at(column, format!("unknown control kind `{kind}`"))
+                })?;
+                line.index(forms.controls.len())?;
+                line.keyword("form")?;
+                let form = line.number("a form index")?.1;
+                let name = line.string()?;
+                let value = line.string()?;
+                let checked = line.flag("checked");
+                forms.controls.push(Control {
+                    kind,
+                    form,
+                    name,
+                    value,
+                    checked,
+                    options: vec![],
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Program> {
+        let text = if self.utf8 {
+            Text::Utf8(self.text)
+        } else {
+            // Every block was checked to be ascii
+            Text::Ascii(AsciiString::from_ascii(self.text).unwrap())
+        };
+        Ok(Program {
+            text,
+            ..self.program
+        })
+    }
+}
+
+fn control_kind(name: &str) -> Option<ControlKind> {
+    (1..=u8::MAX)
+        .map_while(ControlKind::from_byte)
+        .find(|kind| kind.as_str() == name)
+}
+
+fn instruction(line: &mut Line) -> Result<Instruction> {
+    let (column, name) = line.word("an instruction")?;
+    let instruction = match name {
+        "stop" => Instruction::Stop,
+        "text" => Instruction::Text(address_range(line)?),
+        "push" | "pop" => {
+            let push = name == "push";
+            let (column, operand) = line.word("a style, heading or alignment")?;
+            if let Some(level) = operand
+                .strip_prefix('h')
+                .and_then(|level| level.parse().ok())
+            {
+                match push {
+                    true => Instruction::PushHeading(level),
+                    false => Instruction::PopHeading(level),
+                }
+            } else if let Some(align) = align(line, operand)? {
+                match push {
+                    true => Instruction::PushAlign(align),
+                    false => Instruction::PopAlign(align),
+                }
+            } else if let Some(style) = style_var(line, operand)? {
+                match push {
+                    true => Instruction::Push(style),
+                    false => Instruction::Pop(style),
+                }
+            } else {
+                return Err(line.error_at(column, format!("unknown style `{operand}`")));
+            }
+        }
+        "endl" => Instruction::Endl,
+        "link" => Instruction::LinkStart(line.number("a link index")?.1),
+        "endlink" => Instruction::LinkEnd,
+        "list" => {
+            let (column, kind) = line.word("`unordered` or `ordered`")?;
+            match kind {
+                "unordered" => Instruction::ListStart(ListKind::Unordered),
+                "ordered" => {
+                    Instruction::ListStart(ListKind::Ordered(line.number("a start number")?.1))
+                }
+                _ => return Err(line.error_at(column, format!("unknown list kind `{kind}`"))),
+            }
+        }
+        "endlist" => Instruction::ListEnd,
+        "item" => Instruction::ListItem,
+        "table" => Instruction::TableStart,
+        "endtable" => Instruction::TableEnd,
+        "row" => Instruction::TableRow,
+        "cell" => Instruction::TableCell,
+        "headercell" => Instruction::TableHeaderCell,
+        "image" => Instruction::Image(line.number("an image index")?.1),
+        "anchor" => Instruction::Anchor(line.number("an anchor index")?.1),
+        "break" => Instruction::BlockBreak,
+        "paragraph" => Instruction::ParagraphStart,
+        "endparagraph" => Instruction::ParagraphEnd,
+        "indent" => Instruction::Indent(line.number("an indent level")?.1),
+        "rule" => {
+            let thickness = line.number("a thickness")?.1;
+            let (column, style) = line.word("a rule style")?;
+            let style = match style {
+                "solid" => RuleStyle::Solid,
+                "dashed" => RuleStyle::Dashed,
+                "dotted" => RuleStyle::Dotted,
+                "style" => {
+                    let (column, value) = line.number("a rule style")?;
+                    // Known rule styles are written by name, like known styles
+                    match parse_rule_style(value) {
+                        style @ RuleStyle::Unknown(_) => style,
+                        style => return Err(line.error_at(column, format!("rule style {value} is `{style}`"))),
+                    }
+                }
+                _ => return Err(line.error_at(column, format!("unknown rule style `{style}`"))),
+            };
+            Instruction::Rule(Rule { thickness, style })
+        }
+        "pre" => Instruction::PreformattedStart,
+        "endpre" => Instruction::PreformattedEnd,
+        _ => {
+            let control: fn(u32) -> Instruction = match control_kind(name) {
+                Some(ControlKind::TextInput) => Instruction::TextInput,
+                Some(ControlKind::Checkbox) => Instruction::Checkbox,
+                Some(ControlKind::Radio) => Instruction::Radio,
+                Some(ControlKind::Select) => Instruction::Select,
+                Some(ControlKind::Submit) => Instruction::Submit,
+                Some(ControlKind::Hidden) | None => {
+                    return Err(line.error_at(column, format!("unknown instruction `{name}`")))
+                }
+            };
+            control(line.number("a control index")?.1)
+        }
+    };
+    Ok(instruction)
+}
+
+/// Parses a text range like `0x0000..0x0004`, which includes its end, or `0x0004+0` if it's
+/// empty.
+fn address_range(line: &mut Line) -> Result<AddressRange> {
+    let (column, range) = line.word("a text range")?;
+    let invalid = || line.error_at(column, format!("invalid text range `{range}`"));
+    if let Some(base) = range.strip_suffix("+0") {
+        let base = parse_number(base).ok_or_else(invalid)?;
+        return Ok(AddressRange {
+            base: Address(base),
+            range: 0,
+        });
+    }
+    let (base, end) = range.split_once("..").ok_or_else(invalid)?;
+    let base: u32 = parse_number(base).ok_or_else(invalid)?;
+    let end: u32 = parse_number(end).ok_or_else(invalid)?;
+    let range = end
+        .checked_sub(base)
+        .and_then(|len| len.checked_add(1))
+        .ok_or_else(invalid)?;
+    Ok(AddressRange {
+        base: Address(base),
+        range,
+    })
+}
+
+/// Parses a style var, `operand` is the word after `push` or `pop`.
+fn style_var(line: &mut Line, operand: &str) -> Result<Option<StyleVar>> {
+    let style = match operand {
+        "bold" => StyleVar::Bold,
+        "italic" => StyleVar::Italic,
+        "underline" => StyleVar::Underline,
+        "monospace" => StyleVar::Monospace,
+        "strikethrough" => StyleVar::Strikethrough,
+        "superscript" => StyleVar::Superscript,
+        "subscript" => StyleVar::Subscript,
+        "small" => StyleVar::Small,
+        "large" => StyleVar::Large,
+        "style" => {
+            let (column, value) = line.number("a style number")?;
+            // Known styles are written by name, so they're only ever read back as themselves
+            match parse_style_var(value) {
+                Ok(style @ StyleVar::Unknown(_)) => style,
+                Ok(style) => return Err(line.error_at(column, format!("style {value} is `{style}`"))),
+                Err(_) => return Err(line.error_at(column, format!("invalid style number {value}"))),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(style))
+}
+
+/// Parses an alignment, `operand` is the word after `push` or `pop`.
+fn align(line: &mut Line, operand: &str) -> Result<Option<Align>> {
+    let align = match operand {
+        "left" => Align::Left,
+        "center" => Align::Center,
+        "right" => Align::Right,
+        "justify" => Align::Justify,
+        "align" => {
+            let (column, value) = line.number("an alignment number")?;
+            // Known alignments are written by name, like known styles
+            match parse_align_value(value) {
+                align @ Align::Unknown(_) => align,
+                align => return Err(line.error_at(column, format!("align {value} is `{align}`"))),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(align))
+}
+
+/// Parses a decimal number, or a hexadecimal one with a `0x` prefix.
+fn parse_number<T: TryFrom<u64>>(text: &str) -> Option<T> {
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    T::try_from(value).ok()
+}
+
+/// A line of the source, read from left to right.
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(number: usize, text: &'a str) -> Self {
+        Self {
+            number,
+            text,
+            pos: 0,
+        }
+    }
+
+    fn is_blank(&self) -> bool {
+        let text = self.text.trim_start();
+        text.is_empty() || text.starts_with(';')
+    }
+
+    fn starts_with(&self, c: char) -> bool {
+        self.text.starts_with(c)
+    }
+
+    fn starts_with_whitespace(&self) -> bool {
+        self.text.starts_with(char::is_whitespace)
+    }
+
+    /// Column of the current position.
+    fn column(&self) -> usize {
+        self.text[..self.pos].chars().count() + 1
+    }
+
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        self.error_at(self.column(), message)
+    }
+
+    fn error_at(&self, column: usize, message: impl Into<String>) -> AsmError {
+        AsmError {
+            line: self.number,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.text[self.pos..].chars().next()
+    }
+
+    fn peek_word(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        let rest = &self.text[self.pos..];
+        let word = &rest[..rest.find(char::is_whitespace).unwrap_or(rest.len())];
+        (!word.is_empty()).then_some(word)
+    }
+
+    /// Reads the next word, with the column it starts at.
+    fn next_word(&mut self) -> Option<(usize, &'a str)> {
+        let word = self.peek_word()?;
+        let column = self.column();
+        self.pos += word.len();
+        Some((column, word))
+    }
+
+    fn word(&mut self, expected: &str) -> Result<(usize, &'a str)> {
+        self.next_word()
+            .ok_or_else(|| self.error(format!("expected {expected}")))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<()> {
+        match self.next_word() {
+            Some((_, word)) if word == keyword => Ok(()),
+            Some((column, _)) => Err(self.error_at(column, format!("expected `{keyword}`"))),
+            None => Err(self.error(format!("expected `{keyword}`"))),
+        }
+    }
+
+    /// Reads `flag` if it's the next word.
+    fn flag(&mut self, flag: &str) -> bool {
+        let found = self.peek_word() == Some(flag);
+        if found {
+            self.pos += flag.len();
+        }
+        found
+    }
+
+    fn number<T: TryFrom<u64>>(&mut self, expected: &str) -> Result<(usize, T)> {
+        let (column, word) = self.word(expected)?;
+        let number = parse_number(word)
+            .ok_or_else(|| self.error_at(column, format!("expected {expected}")))?;
+        Ok((column, number))
+    }
+
+    /// Reads the index of an entry, which must be the next one in its table.
+    fn index(&mut self, expected: usize) -> Result<()> {
+        let (column, index) = self.number::<usize>("an index")?;
+        if index != expected {
+            return Err(self.error_at(column, format!("expected index {expected}")));
+        }
+        Ok(())
+    }
+
+    /// Reads the offset of a block of data, which must be where the previous block ended.
+    fn address(&mut self, expected: usize) -> Result<()> {
+        let (column, address) = self.number::<usize>("an address")?;
+        if address != expected {
+            return Err(self.error_at(column, format!("expected address {expected:#06x}")));
+        }
+        Ok(())
+    }
+
+    /// Reads hexadecimal bytes until the end of the line.
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        while let Some((column, word)) = self.next_word() {
+            let byte = match word.len() {
+                1 | 2 => u8::from_str_radix(word, 16).ok(),
+                _ => None,
+            };
+            bytes
+                .push(byte.ok_or_else(|| self.error_at(column, format!("invalid byte `{word}`")))?);
+        }
+        Ok(bytes)
+    }
+
+    /// Reads the rest of the line without leading whitespace, with the column it starts at.
+    fn rest(&mut self) -> (usize, &'a str) {
+        self.skip_whitespace();
+        let column = self.column();
+        let rest = &self.text[self.pos..];
+        self.pos = self.text.len();
+        (column, rest)
+    }
+
+    /// Reads a quoted string, with the escapes of Rust strings.
+    fn string(&mut self) -> Result<String> {
+        if self.peek() != Some('"') {
+            return Err(self.error("expected a quoted string"));
+        }
+        let start = self.column();
+        let mut result = String::new();
+        let mut chars = self.text[self.pos..].char_indices().skip(1);
+        while let Some((offset, c)) = chars.next() {
+            let escape_column =
+                self.column() + self.text[self.pos..self.pos + offset].chars().count();
+            match c {
+                '"' => {
+                    self.pos += offset + 1;
+                    return Ok(result);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('n') => Some('\n'),
+                        Some('r') => Some('\r'),
+                        Some('t') => Some('\t'),
+                        Some('0') => Some('\0'),
+                        Some(c @ ('\\' | '"' | '\'')) => Some(c),
+                        Some('u') => {
+                            let rest = &self.text[self.pos + offset + 2..];
+                            let code = rest
+                                .strip_prefix('{')
+                                .and_then(|rest| rest.split_once('}'))
+                                .map(|(code, _)| code);
+                            let c = code
+                                .and_then(|code| u32::from_str_radix(code, 16).ok())
+                                .and_then(char::from_u32);
+                            if let (Some(code), Some(_)) = (code, c) {
+                                // Skip the braces and the digits
+                                chars.nth(code.len() + 1);
+                            }
+                            c
+                        }
+                        _ => None,
+                    };
+                    result.push(
+                        escaped.ok_or_else(|| self.error_at(escape_column, "invalid escape"))?,
+                    );
+                }
+                c => result.push(c),
+            }
+        }
+        Err(self.error_at(start, "unterminated string"))
+    }
+
+    /// Checks that nothing but whitespace is left.
+    fn end(&mut self) -> Result<()> {
+        match self.peek() {
+            Some(_) => Err(self.error("unexpected text at end of line")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Forms, Metadata};
+
+    #[test]
+    fn test_round_trip() {
+        let mut bitmap = Bitmap::new(3, 2);
+        bitmap.set_pixel(0, 0, true);
+        bitmap.set_pixel(2, 1, true);
+        let program = Program {
+            text: Text::Utf8(String::from("Tab\tand \"quotes\"\nover ünicode lines")),
+            code: vec![
+                Instruction::PushHeading(1),
+                Instruction::PushAlign(Align::Center),
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 3,
+                }),
+                Instruction::Text(AddressRange {
+                    base: Address(4),
+                    range: 0,
+                }),
+                Instruction::PopAlign(Align::Center),
+                Instruction::PushAlign(parse_align_value(7)),
+                Instruction::PopHeading(1),
+                Instruction::Push(parse_style_var(0x80).unwrap()),
+                Instruction::LinkStart(0),
+                Instruction::Anchor(0),
+                Instruction::ListStart(ListKind::Ordered(3)),
+                Instruction::Rule(Rule {
+                    thickness: 2,
+                    style: parse_rule_style(9),
+                }),
+                Instruction::Image(1),
+                Instruction::Select(1),
+                Instruction::Stop,
+            ],
+            meta: Metadata {
+                title: Some(String::from("A \\ title")),
+                timestamp: Some(1700000000),
+                ..Default::default()
+            },
+            links: vec![
+                Link::Url(String::from("https://example.com")),
+                Link::Jump(8),
+                Link::Unknown {
+                    kind: 9,
+                    data: vec![0xab, 0x01],
+                },
+            ],
+            images: vec![
+                Image::Mono(bitmap),
+                Image::Unknown {
+                    format: 7,
+                    data: vec![],
+                },
+            ],
+            forms: Forms {
+                forms: vec![Form {
+                    method: FormMethod::Post,
+                    action: String::from("/search"),
+                }],
+                controls: vec![
+                    Control {
+                        kind: ControlKind::Hidden,
+                        form: 0,
+                        name: String::from("token"),
+                        value: String::from("1"),
+                        checked: true,
+                        options: vec![],
+                    },
+                    Control {
+                        kind: ControlKind::Select,
+                        form: 0,
+                        name: String::from("lang"),
+                        value: String::new(),
+                        checked: false,
+                        options: vec![SelectOption {
+                            value: String::from("nl"),
+                            label: String::from("Dutch"),
+                            selected: true,
+                        }],
+                    },
+                ],
+            },
+            anchors: vec![
+                Anchor::Named {
+                    name: String::from("top"),
+                    instruction: 0,
+                },
+                Anchor::Unknown {
+                    kind: 5,
+                    data: vec![0x10],
+                },
+            ],
+            sections: vec![Section {
+                ty: SectionType(0xf00),
+                data: (0..20).collect(),
+            }],
+        };
+        let source = program.to_string();
+        assert_eq!(assemble(&source), Ok(program));
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = ".data\n\t0x0000\t\"Hi\"\n.text\n\ttext 0x0000..0x0001\n\tpush blink\n";
+        let err = assemble(source).unwrap_err();
+        assert_eq!((err.line, err.column), (5, 7));
+        assert_eq!(err.to_string(), "5:7: unknown style `blink`");
+
+        let err = assemble(".data\n\t0x0001\t\"Hi\"\n").unwrap_err();
+        assert_eq!(err.to_string(), "2:2: expected address 0x0000");
+
+        let err = assemble(".text\n\tpush style 1\n").unwrap_err();
+        assert_eq!(err.to_string(), "2:13: style 1 is `bold`");
+
+        let err = assemble(".text\n\tpop align 3\n").unwrap_err();
+        assert_eq!(err.to_string(), "2:12: align 3 is `justify`");
+
+        let err = assemble(".text\n\trule 1 style 2\n").unwrap_err();
+        assert_eq!(err.to_string(), "2:15: rule style 2 is `dotted`");
+
+        let err = assemble(".meta\n\ttitle\t\"Bad \\q\"\n").unwrap_err();
+        assert_eq!(err.to_string(), "2:13: invalid escape");
+    }
+}
@@ -0,0 +1,178 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::instruction::unpack_u64;
+use crate::instruction::{pack_u32_into_u64, Operand};
+use crate::{BinaryInstruction, ErrorKind, Result};
+
+/// How the code section is stored, selected with the [`crate::Flags::COMPACT_CODE`] header flag.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum CodeEncoding {
+    /// Every instruction is a [`BinaryInstruction`] of [`BinaryInstruction::ENCODED_SIZE`] bytes.
+    #[default]
+    Fixed,
+    /// Every instruction is an opcode byte followed by LEB128 encoded operands.
+    /// Text ranges store their base as a signed (zigzag) delta from the end of the previous
+    /// text range, followed by their length.
+    Compact,
+}
+
+/// Maximum length of a LEB128 encoded u64
+pub const MAX_VARINT_SIZE: usize = 10;
+
+/// Encodes `value` as LEB128 into `out`, returning the number of bytes used.
+pub fn encode_varint(mut value: u64, out: &mut [u8; MAX_VARINT_SIZE]) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out[len] = byte;
+            return len + 1;
+        }
+        out[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub fn write_varint(value: u64, out: &mut Vec<u8>) {
+    let mut buf = [0; MAX_VARINT_SIZE];
+    let len = encode_varint(value, &mut buf);
+    out.extend_from_slice(&buf[..len]);
+}
+
+/// Reads a LEB128 encoded value from the start of `bytes`, returning the value and the
+/// number of bytes read. Offsets in errors are relative to the start of `bytes`.
+pub fn read_varint(bytes: &[u8]) -> Result<(u64, usize)> {
+    let mut value: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate().take(MAX_VARINT_SIZE) {
+        let bits = (byte & 0x7f) as u64;
+        // The last byte may only contribute a single bit
+        if i == MAX_VARINT_SIZE - 1 && bits > 1 {
+            return Err(crate::Error::at(i, ErrorKind::InvalidVarint));
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    if bytes.len() >= MAX_VARINT_SIZE {
+        return Err(crate::Error::at(MAX_VARINT_SIZE - 1, ErrorKind::InvalidVarint));
+    }
+    Err(crate::Error::at(
+        0,
+        ErrorKind::Truncated {
+            needed: bytes.len() + 1,
+            available: bytes.len(),
+        },
+    ))
+}
+
+#[cfg(feature = "alloc")]
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Encodes instructions in the compact encoding. Text ranges are delta encoded, so all
+/// instructions of a program must go through the same encoder, in order.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default, Clone)]
+pub struct CompactEncoder {
+    prev_end: u32,
+}
+
+#[cfg(feature = "alloc")]
+impl CompactEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn encode(&mut self, instruction: &BinaryInstruction, out: &mut Vec<u8>) {
+        out.push(instruction.ty);
+        match Operand::of(instruction.ty) {
+            Some(Operand::None) => {}
+            Some(Operand::TextRange) => {
+                let (range, base) = unpack_u64(instruction.arg);
+                write_varint(zigzag_encode(base as i64 - self.prev_end as i64), out);
+                write_varint(range as u64, out);
+                self.prev_end = base.wrapping_add(range);
+            }
+            // Unknown instructions can't be decoded anyway, but we don't want to lose their argument.
+            Some(Operand::Value) | None => write_varint(instruction.arg, out),
+        }
+    }
+}
+
+/// Decodes instructions in the compact encoding, the counterpart of [`CompactEncoder`].
+#[derive(Debug, Default, Clone)]
+pub struct CompactDecoder {
+    prev_end: u32,
+}
+
+impl CompactDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes the instruction at the start of `bytes`, returning it and its encoded size.
+    /// Offsets in errors are relative to the start of `bytes`.
+    pub fn decode(&mut self, bytes: &[u8]) -> Result<(BinaryInstruction, usize)> {
+        let ty = *bytes.first().ok_or(crate::Error::at(
+            0,
+            ErrorKind::Truncated {
+                needed: 1,
+                available: 0,
+            },
+        ))?;
+        let operand = Operand::of(ty).ok_or(crate::Error::at(0, ErrorKind::InvalidOpcode { opcode: ty }))?;
+        let read_at = |pos: usize| read_varint(&bytes[pos..]).map_err(|e| e.with_offset(pos));
+        match operand {
+            Operand::None => Ok((BinaryInstruction { ty, arg: 0 }, 1)),
+            Operand::Value => {
+                let (arg, len) = read_at(1)?;
+                Ok((BinaryInstruction { ty, arg }, 1 + len))
+            }
+            Operand::TextRange => {
+                let (delta, delta_len) = read_at(1)?;
+                let (range, range_len) = read_at(1 + delta_len)?;
+                let base = u32::try_from(self.prev_end as i64 + zigzag_decode(delta))
+                    .map_err(|_| crate::Error::at(1, ErrorKind::InvalidOperand { opcode: ty }))?;
+                let range = u32::try_from(range)
+                    .map_err(|_| crate::Error::at(1 + delta_len, ErrorKind::InvalidOperand { opcode: ty }))?;
+                self.prev_end = base.wrapping_add(range);
+                let arg = pack_u32_into_u64(base, range);
+                Ok((BinaryInstruction { ty, arg }, 1 + delta_len + range_len))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = [0; MAX_VARINT_SIZE];
+            let len = encode_varint(value, &mut buf);
+            let buf = &buf[..len];
+            assert_eq!(read_varint(buf).unwrap(), (value, buf.len()));
+            assert!(read_varint(&buf[..buf.len() - 1]).is_err());
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_zigzag_roundtrip() {
+        for value in [0, 1, -1, i64::MAX, i64::MIN] {
+            assert_eq!(zigzag_decode(zigzag_encode(value)), value);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+#[cfg(not(feature = "std"))]
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::convert::TryInto;
+
+#[cfg(feature = "alloc")]
+use alloc::vec;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{ErrorKind, Result};
+
+/// How the data section is stored, selected with the [`crate::Flags::COMPRESSED_TEXT`] header flag.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum TextCompression {
+    #[default]
+    None,
+    /// LZSS with a 4 KiB window. The compressed section starts with the decompressed length
+    /// as a little endian u32, followed by groups of a flag byte and 8 tokens. Each flag bit
+    /// (least significant first) marks its token as either a literal byte (0), or a 2 byte
+    /// back reference (1) holding a 12 bit distance and a 4 bit length.
+    Lzss,
+}
+
+#[cfg(feature = "alloc")]
+const WINDOW_SIZE: usize = 1 << 12;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = MIN_MATCH + 0xf;
+/// Limits how many earlier positions are tried when looking for a match.
+#[cfg(feature = "alloc")]
+const MAX_CHAIN: usize = 64;
+/// A group of 8 tokens can at most produce 8 maximum length matches from 17 bytes of input.
+const MAX_RATIO: usize = 8 * MAX_MATCH / 17 + 1;
+#[cfg(feature = "alloc")]
+const HASH_SIZE: usize = 1 << 12;
+
+#[cfg(feature = "alloc")]
+fn hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as usize) << 8 ^ (bytes[1] as usize) << 4 ^ bytes[2] as usize;
+    value % HASH_SIZE
+}
+
+/// Compresses `input` with [`TextCompression::Lzss`].
+#[cfg(feature = "alloc")]
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 4);
+    out.extend_from_slice(&(input.len() as u32).to_le_bytes());
+
+    // Most recent position for every hash, and the previous position with the same hash
+    // for every position.
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut prev = vec![usize::MAX; input.len()];
+    let insert = |pos: usize, head: &mut [usize], prev: &mut [usize]| {
+        if pos + MIN_MATCH <= input.len() {
+            let h = hash(&input[pos..]);
+            prev[pos] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    let mut flag_index = 0;
+    let mut token = 8;
+    while pos < input.len() {
+        if token == 8 {
+            flag_index = out.len();
+            out.push(0);
+            token = 0;
+        }
+
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if pos + MIN_MATCH <= input.len() {
+            let mut candidate = head[hash(&input[pos..])];
+            let mut chain = 0;
+            while candidate != usize::MAX && pos - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let max = MAX_MATCH.min(input.len() - pos);
+                let len = input[candidate..]
+                    .iter()
+                    .zip(&input[pos..pos + max])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = pos - candidate;
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            out[flag_index] |= 1 << token;
+            let value = ((best_dist - 1) << 4 | (best_len - MIN_MATCH)) as u16;
+            out.extend_from_slice(&value.to_le_bytes());
+            for p in pos..pos + best_len {
+                insert(p, &mut head, &mut prev);
+            }
+            pos += best_len;
+        } else {
+            out.push(input[pos]);
+            insert(pos, &mut head, &mut prev);
+            pos += 1;
+        }
+        token += 1;
+    }
+    out
+}
+
+/// Returns the decompressed size of a compressed data section, so callers can size their
+/// scratch buffer. Fails if the size is larger than the input could possibly expand to.
+pub fn decompressed_len(input: &[u8]) -> Result<usize> {
+    let len = input.get(0..4).ok_or(crate::Error::at(
+        0,
+        ErrorKind::Truncated {
+            needed: 4,
+            available: input.len(),
+        },
+    ))?;
+    let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    if len > (input.len() - 4) * MAX_RATIO {
+        return Err(crate::Error::at(0, ErrorKind::InvalidCompressedData));
+    }
+    Ok(len)
+}
+
+/// Decompresses a data section compressed with [`TextCompression::Lzss`] into `scratch`,
+/// returning the decompressed bytes. Does not allocate, so this works under `no_std`.
+/// Offsets in errors are relative to the start of `input`.
+pub fn decompress<'a>(input: &[u8], scratch: &'a mut [u8]) -> Result<&'a [u8]> {
+    let len = decompressed_len(input)?;
+    if scratch.len() < len {
+        return Err(crate::Error::new(ErrorKind::ScratchTooSmall { needed: len }));
+    }
+    let out = &mut scratch[..len];
+    let truncated = |pos: usize, needed: usize| {
+        crate::Error::at(
+            pos,
+            ErrorKind::Truncated {
+                needed,
+                available: input.len() - pos,
+            },
+        )
+    };
+
+    let mut pos = 4;
+    let mut written = 0;
+    while written < len {
+        let flags = *input.get(pos).ok_or(truncated(pos, 1))?;
+        pos += 1;
+        for token in 0..8 {
+            if written == len {
+                break;
+            }
+            if flags & (1 << token) == 0 {
+                out[written] = *input.get(pos).ok_or(truncated(pos, 1))?;
+                written += 1;
+                pos += 1;
+            } else {
+                let value = input.get(pos..pos + 2).ok_or(truncated(pos, 2))?;
+                let value = u16::from_le_bytes(value.try_into().unwrap()) as usize;
+                let dist = (value >> 4) + 1;
+                let count = (value & 0xf) + MIN_MATCH;
+                if dist > written || written + count > len {
+                    return Err(crate::Error::at(pos, ErrorKind::InvalidCompressedData));
+                }
+                // Copy byte by byte, since a match may overlap with its own output
+                for i in written..written + count {
+                    out[i] = out[i - dist];
+                }
+                written += count;
+                pos += 2;
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let input = b"Read more. Share. Read more. Share. aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa, Read more!";
+        let compressed = compress(input);
+        assert!(compressed.len() < input.len());
+        let mut scratch = [0; 128];
+        assert_eq!(decompress(&compressed, &mut scratch).unwrap(), input);
+
+        let mut small = [0; 16];
+        assert_eq!(
+            decompress(&compressed, &mut small).unwrap_err().kind,
+            ErrorKind::ScratchTooSmall { needed: input.len() }
+        );
+        for len in 0..compressed.len() {
+            assert!(decompress(&compressed[..len], &mut scratch).is_err());
+        }
+    }
+}
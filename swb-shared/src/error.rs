@@ -16,17 +16,221 @@ use std::fmt;
 
 pub type Result<T> = result::Result<T, Error>;
 
-pub struct Error(pub &'static str);
+/// What went wrong while decoding a program.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// The buffer does not start with the SWB magic bytes.
+    InvalidMagic,
+    /// The file was written with a format version this reader doesn't support,
+    /// usually because it is newer.
+    UnsupportedVersion { version: u16 },
+    /// The header has flags set that this reader doesn't know about.
+    UnsupportedFlags { flags: u16 },
+    /// The buffer ended before a complete structure could be read.
+    Truncated { needed: usize, available: usize },
+    /// A required section is missing from the section table.
+    MissingSection { ty: u32 },
+    /// A section this reader interprets appears more than once in the section table.
+    DuplicateSection { ty: u32 },
+    /// A section lies outside of the file, or overlaps the header or section table.
+    SectionOutOfBounds { ty: u32, offset: u32, len: u32 },
+    /// Two sections of known types overlap.
+    OverlappingSections { ty: u32 },
+    /// The data section contains a non-ascii byte.
+    NonAsciiText { byte: u8 },
+    /// The data section is marked as UTF-8, but isn't valid UTF-8.
+    InvalidUtf8,
+    /// The data section is UTF-8, but the reader only supports ascii.
+    Utf8NotSupported,
+    /// Unknown instruction opcode.
+    InvalidOpcode { opcode: u8 },
+    /// The file was corrupted, its checksum doesn't match its contents.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The compressed data section is corrupt.
+    InvalidCompressedData,
+    /// The scratch buffer passed for decompression is smaller than the decompressed data, or
+    /// the buffer passed to a [`crate::SubmissionWriter`] is too small for the submission.
+    ScratchTooSmall { needed: usize },
+    /// A LEB128 encoded value in the compact code encoding is longer than 64 bits.
+    InvalidVarint,
+    /// An operand of a compactly encoded instruction is out of range.
+    InvalidOperand { opcode: u8 },
+    /// A record in the metadata section has an invalid value.
+    InvalidMetadata { key: u8 },
+    /// A heading instruction with a level outside of 1 to 6.
+    InvalidHeadingLevel { level: u64 },
+    /// An entry in the link table has an invalid target.
+    InvalidLinkTarget { kind: u8 },
+    /// A link instruction refers to a link that isn't in the link table.
+    LinkOutOfBounds { index: u32, count: usize },
+    /// An anchor in the anchor table is malformed.
+    InvalidAnchor { kind: u8 },
+    /// An anchor instruction refers to an anchor that isn't in the anchor table.
+    AnchorOutOfBounds { index: u32, count: usize },
+    /// A link or anchor refers to an instruction past the end of the code.
+    JumpOutOfBounds { instruction: u32, count: usize },
+    /// An image in the image table is malformed.
+    InvalidImage { format: u8 },
+    /// An image instruction refers to an image that isn't in the image table.
+    ImageOutOfBounds { index: u32, count: usize },
+    /// A record in the form table is malformed. Submissions use key 0.
+    InvalidForm { key: u8 },
+    /// A control in the form table belongs to a form that isn't in the table.
+    FormOutOfBounds { control: u32, count: usize },
+    /// A control instruction refers to a control that isn't in the form table.
+    ControlOutOfBounds { index: u32, count: usize },
+    /// A control instruction refers to a control of another kind.
+    ControlKindMismatch { index: u32 },
+    /// A push or pop instruction with a style var that is zero or doesn't fit in 8 bits.
+    InvalidStyleVar { value: u64 },
+    /// A text instruction refers to bytes outside the data section.
+    TextOutOfBounds { base: u32, range: u32, text_len: usize },
+    /// A text instruction starts or ends in the middle of a UTF-8 character.
+    TextSplitsCharacter { base: u32, range: u32 },
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// Byte offset into the decoded buffer where the error was found, if known.
+    pub offset: Option<usize>,
+    /// Index of the offending instruction in the code section, if any.
+    pub instruction: Option<usize>,
+}
+
+impl Error {
+    pub const fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            offset: None,
+            instruction: None,
+        }
+    }
+
+    pub const fn at(offset: usize, kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            offset: Some(offset),
+            instruction: None,
+        }
+    }
+
+    /// Attach a byte offset to this error. Offsets are relative to the start of the buffer
+    /// being decoded, so callers decoding a subslice can use this to make the offset absolute.
+    pub fn with_offset(self, offset: usize) -> Self {
+        Self {
+            offset: Some(offset + self.offset.unwrap_or(0)),
+            ..self
+        }
+    }
+
+    pub fn with_instruction(self, index: usize) -> Self {
+        Self {
+            instruction: Some(index),
+            ..self
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::InvalidMagic => write!(f, "not an SWB file"),
+            ErrorKind::UnsupportedVersion { version } => write!(
+                f,
+                "unsupported format version {version} (supported are {} to {})",
+                crate::MIN_FORMAT_VERSION,
+                crate::FORMAT_VERSION
+            ),
+            ErrorKind::UnsupportedFlags { flags } => write!(f, "unsupported header flags {flags:#06x}"),
+            ErrorKind::Truncated { needed, available } => {
+                write!(f, "truncated input, needed {needed} bytes but only {available} are available")
+            }
+            ErrorKind::MissingSection { ty } => write!(f, "missing section of type {ty:#x}"),
+            ErrorKind::DuplicateSection { ty } => write!(f, "duplicate section of type {ty:#x}"),
+            ErrorKind::SectionOutOfBounds { ty, offset, len } => write!(
+                f,
+                "section of type {ty:#x} at {offset:#x} with {len} bytes is out of bounds"
+            ),
+            ErrorKind::OverlappingSections { ty } => {
+                write!(f, "section of type {ty:#x} overlaps another section")
+            }
+            ErrorKind::NonAsciiText { byte } => write!(f, "non-ascii byte {byte:#04x} in data section"),
+            ErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8 in data section"),
+            ErrorKind::Utf8NotSupported => write!(f, "UTF-8 text is not supported"),
+            ErrorKind::InvalidOpcode { opcode } => write!(f, "invalid instruction opcode {opcode:#04x}"),
+            ErrorKind::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum mismatch, expected {expected:#010x} but got {actual:#010x}")
+            }
+            ErrorKind::InvalidCompressedData => write!(f, "corrupt compressed data section"),
+            ErrorKind::ScratchTooSmall { needed } => {
+                write!(f, "scratch buffer too small, {needed} bytes are needed")
+            }
+            ErrorKind::InvalidVarint => write!(f, "invalid variable length integer"),
+            ErrorKind::InvalidOperand { opcode } => {
+                write!(f, "operand out of range for instruction opcode {opcode:#04x}")
+            }
+            ErrorKind::InvalidMetadata { key } => write!(f, "invalid metadata record with key {key:#04x}"),
+            ErrorKind::InvalidHeadingLevel { level } => write!(f, "invalid heading level {level}"),
+            ErrorKind::InvalidLinkTarget { kind } => write!(f, "invalid link target of kind {kind:#04x}"),
+            ErrorKind::LinkOutOfBounds { index, count } => {
+                write!(f, "link {index} is outside of the link table with {count} links")
+            }
+            ErrorKind::InvalidAnchor { kind } => write!(f, "invalid anchor of kind {kind:#04x}"),
+            ErrorKind::AnchorOutOfBounds { index, count } => {
+                write!(f, "anchor {index} is outside of the anchor table with {count} anchors")
+            }
+            ErrorKind::JumpOutOfBounds { instruction, count } => {
+                write!(f, "jump to instruction {instruction} is past the end of the {count} instructions")
+            }
+            ErrorKind::InvalidImage { format } => write!(f, "invalid image of format {format:#04x}"),
+            ErrorKind::ImageOutOfBounds { index, count } => {
+                write!(f, "image {index} is outside of the image table with {count} images")
+            }
+            ErrorKind::InvalidForm { key } => write!(f, "invalid form record with key {key:#04x}"),
+            ErrorKind::FormOutOfBounds { control, count } => {
+                write!(f, "control {control} belongs to a form outside of the form table with {count} forms")
+            }
+            ErrorKind::ControlOutOfBounds { index, count } => {
+                write!(f, "control {index} is outside of the form table with {count} controls")
+            }
+            ErrorKind::ControlKindMismatch { index } => {
+                write!(f, "control {index} is of another kind than its instruction")
+            }
+            ErrorKind::InvalidStyleVar { value } => write!(f, "invalid style var encoding {value:#x}"),
+            ErrorKind::TextOutOfBounds { base, range, text_len } => write!(
+                f,
+                "text range {base:#06x}+{range} is outside of the {text_len} byte data section"
+            ),
+            ErrorKind::TextSplitsCharacter { base, range } => {
+                write!(f, "text range {base:#06x}+{range} splits a UTF-8 character")
+            }
+        }
+    }
+}
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{self}")
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.kind)?;
+        if let Some(index) = self.instruction {
+            write!(f, " in instruction {index}")?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at byte offset {offset:#x}")?;
+        }
+        Ok(())
     }
 }
 
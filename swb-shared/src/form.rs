@@ -0,0 +1,764 @@
+#[cfg(not(feature = "std"))]
+use core::{fmt, str};
+#[cfg(feature = "std")]
+use std::{fmt, str};
+
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::section::write_record;
+use crate::section::read_record;
+#[cfg(feature = "alloc")]
+use crate::write_varint;
+use crate::{encode_varint, read_varint, ErrorKind, Result, MAX_VARINT_SIZE};
+
+/// Keys of the records in a form table.
+pub mod record {
+    /// A [`crate::FormView`].
+    pub const FORM: u8 = 1;
+    /// A [`crate::ControlView`].
+    pub const CONTROL: u8 = 2;
+}
+
+/// How a form is submitted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FormMethod {
+    /// The values are appended to the action URL as a query string.
+    Get = 1,
+    /// The values are sent as an `application/x-www-form-urlencoded` body.
+    Post = 2,
+}
+
+impl FormMethod {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(FormMethod::Get),
+            2 => Some(FormMethod::Post),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FormMethod::Get => "get",
+            FormMethod::Post => "post",
+        }
+    }
+}
+
+/// What kind of form control an entry in the form table is. Every kind except
+/// [`ControlKind::Hidden`] is shown by the instruction of the same name.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ControlKind {
+    /// A text box, the value is the initial text.
+    TextInput = 1,
+    /// Sends its value if it's checked.
+    Checkbox = 2,
+    /// Sends its value if it's checked, only one radio button with the same name in a form
+    /// can be checked.
+    Radio = 3,
+    /// Sends the values of its selected options.
+    Select = 4,
+    /// Submits the form, the value is both its label and what it sends. Only the button that
+    /// was pressed is sent, and only if it has a name.
+    Submit = 5,
+    /// Isn't shown, but always sends its value.
+    Hidden = 6,
+}
+
+impl ControlKind {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(ControlKind::TextInput),
+            2 => Some(ControlKind::Checkbox),
+            3 => Some(ControlKind::Radio),
+            4 => Some(ControlKind::Select),
+            5 => Some(ControlKind::Submit),
+            6 => Some(ControlKind::Hidden),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ControlKind::TextInput => "input",
+            ControlKind::Checkbox => "checkbox",
+            ControlKind::Radio => "radio",
+            ControlKind::Select => "select",
+            ControlKind::Submit => "submit",
+            ControlKind::Hidden => "hidden",
+        }
+    }
+}
+
+/// Reads the fields of a record value, see [`ControlView`] and [`Submission`] for layouts.
+struct Fields<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    key: u8,
+}
+
+impl<'a> Fields<'a> {
+    fn new(bytes: &'a [u8], key: u8) -> Self {
+        Self { bytes, pos: 0, key }
+    }
+
+    fn invalid(&self) -> crate::Error {
+        crate::Error::at(self.pos, ErrorKind::InvalidForm { key: self.key })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let byte = *self.bytes.get(self.pos).ok_or(self.invalid())?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let (value, len) = read_varint(&self.bytes[self.pos..]).map_err(|_| self.invalid())?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    /// A LEB128 encoded length followed by that many bytes of UTF-8.
+    fn str(&mut self) -> Result<&'a str> {
+        let len = self.varint()?;
+        let bytes = usize::try_from(len)
+            .ok()
+            .and_then(|len| self.bytes.get(self.pos..self.pos.checked_add(len)?))
+            .ok_or(self.invalid())?;
+        let text = str::from_utf8(bytes).map_err(|_| self.invalid())?;
+        self.pos += bytes.len();
+        Ok(text)
+    }
+
+    /// The rest of the value as UTF-8.
+    fn rest(&mut self) -> Result<&'a str> {
+        let text = str::from_utf8(&self.bytes[self.pos..]).map_err(|_| self.invalid())?;
+        self.pos = self.bytes.len();
+        Ok(text)
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn write_str(value: &str, out: &mut Vec<u8>) {
+    write_varint(value.len() as u64, out);
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// A form, which controls refer to by its index among the forms in the table.
+///
+/// Layout: the method byte, followed by the action URL as UTF-8.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FormView<'a> {
+    pub method: FormMethod,
+    /// Where the form is submitted to
+    pub action: &'a str,
+}
+
+/// A form control, which instructions refer to by its index among the controls in the table.
+///
+/// Layout:
+/// - kind byte, see [`ControlKind`]
+/// - flags byte, bit 0 is set if the control is checked
+/// - LEB128 encoded index of the form the control belongs to
+/// - name and value, each a LEB128 encoded length followed by UTF-8
+/// - for selects, the options until the end of the record: a flags byte with bit 0 set if the
+///   option is selected, followed by its value and label in the same way as the name
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ControlView<'a> {
+    pub kind: ControlKind,
+    pub form: u32,
+    pub name: &'a str,
+    pub value: &'a str,
+    /// Whether a checkbox or radio button starts out checked
+    pub checked: bool,
+    options: &'a [u8],
+}
+
+impl<'a> ControlView<'a> {
+    fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let mut fields = Fields::new(bytes, record::CONTROL);
+        let kind = fields.byte()?;
+        let kind = ControlKind::from_byte(kind).ok_or(fields.invalid())?;
+        let checked = fields.byte()? & 1 != 0;
+        let form = fields.varint()?;
+        let form = u32::try_from(form).map_err(|_| fields.invalid())?;
+        let name = fields.str()?;
+        let value = fields.str()?;
+        let control = Self {
+            kind,
+            form,
+            name,
+            value,
+            checked,
+            options: &bytes[fields.pos..],
+        };
+        // Validate the options up front, so iterating over them can't fail
+        for option in control.options_inner() {
+            option.map_err(|e| e.with_offset(fields.pos))?;
+        }
+        Ok(control)
+    }
+
+    fn options_inner(&self) -> impl Iterator<Item = Result<SelectOptionView<'a>>> {
+        let mut fields = Fields::new(self.options, record::CONTROL);
+        core::iter::from_fn(move || {
+            if fields.is_empty() {
+                return None;
+            }
+            let option = (|| {
+                let selected = fields.byte()? & 1 != 0;
+                let value = fields.str()?;
+                let label = fields.str()?;
+                Ok(SelectOptionView {
+                    value,
+                    label,
+                    selected,
+                })
+            })();
+            if option.is_err() {
+                fields.pos = fields.bytes.len();
+            }
+            Some(option)
+        })
+    }
+
+    /// The options of a select, in the order they're shown.
+    pub fn options(&self) -> impl Iterator<Item = SelectOptionView<'a>> {
+        // Checked by decode
+        self.options_inner().map(|option| option.unwrap())
+    }
+}
+
+/// An option of a [`ControlKind::Select`] control.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SelectOptionView<'a> {
+    pub value: &'a str,
+    pub label: &'a str,
+    pub selected: bool,
+}
+
+/// An entry in a form table.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FormRecord<'a> {
+    Form(FormView<'a>),
+    Control(ControlView<'a>),
+}
+
+/// Iterates over the records of an encoded [`crate::SectionType::FORMS`] section. Iteration
+/// stops after the first error.
+///
+/// The section is a list of records, each a key byte (see [`record`]), the LEB128 encoded
+/// length of the value and the value itself. Records with unknown keys are skipped.
+#[derive(Debug, Clone)]
+pub struct FormTable<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> FormTable<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// The forms in the table, in the order of their index.
+    pub fn forms(&self) -> impl Iterator<Item = Result<FormView<'a>>> {
+        self.clone().filter_map(|record| match record {
+            Ok(FormRecord::Form(form)) => Some(Ok(form)),
+            Ok(FormRecord::Control(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// The controls in the table, in the order of their index.
+    pub fn controls(&self) -> impl Iterator<Item = Result<ControlView<'a>>> {
+        self.clone().filter_map(|record| match record {
+            Ok(FormRecord::Control(control)) => Some(Ok(control)),
+            Ok(FormRecord::Form(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    pub fn form(&self, index: u32) -> Option<Result<FormView<'a>>> {
+        self.forms().nth(index as usize)
+    }
+
+    /// Returns the control with the given index, as used by control instructions like
+    /// [`crate::Instruction::TextInput`].
+    pub fn control(&self, index: u32) -> Option<Result<ControlView<'a>>> {
+        self.controls().nth(index as usize)
+    }
+
+    /// Decodes every record, and checks that every control belongs to a form in the table.
+    /// Returns the number of controls.
+    pub fn validate(&self) -> Result<usize> {
+        let mut forms = 0;
+        let mut controls = 0;
+        for record in self.clone() {
+            match record? {
+                FormRecord::Form(_) => forms += 1,
+                FormRecord::Control(_) => controls += 1,
+            }
+        }
+        for (index, control) in self.controls().enumerate() {
+            if control?.form as usize >= forms {
+                return Err(crate::Error::new(ErrorKind::FormOutOfBounds {
+                    control: index as u32,
+                    count: forms,
+                }));
+            }
+        }
+        Ok(controls)
+    }
+
+    fn decode_next(&mut self) -> Result<Option<FormRecord<'a>>> {
+        let (key, value, len) = read_record(&self.bytes[self.pos..])?;
+        let record = match key {
+            record::FORM => {
+                let mut fields = Fields::new(value, key);
+                let method = fields.byte()?;
+                let method = FormMethod::from_byte(method).ok_or(fields.invalid())?;
+                let action = fields.rest()?;
+                Some(FormRecord::Form(FormView { method, action }))
+            }
+            record::CONTROL => Some(FormRecord::Control(ControlView::decode(value)?)),
+            _ => None,
+        };
+        self.pos += len;
+        Ok(record)
+    }
+}
+
+impl<'a> Iterator for FormTable<'a> {
+    type Item = Result<FormRecord<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.bytes.len() {
+            let start = self.pos;
+            match self.decode_next() {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.pos = self.bytes.len();
+                    return Some(Err(e.with_offset(start)));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Owned version of [`FormView`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Form {
+    pub method: FormMethod,
+    pub action: String,
+}
+
+/// Owned version of [`SelectOptionView`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SelectOption {
+    pub value: String,
+    pub label: String,
+    pub selected: bool,
+}
+
+/// Owned version of [`ControlView`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Control {
+    pub kind: ControlKind,
+    pub form: u32,
+    pub name: String,
+    pub value: String,
+    pub checked: bool,
+    pub options: Vec<SelectOption>,
+}
+
+/// The forms and controls of a program, see [`FormTable`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Forms {
+    pub forms: Vec<Form>,
+    pub controls: Vec<Control>,
+}
+
+#[cfg(feature = "alloc")]
+impl Forms {
+    /// Whether there are no forms, in which case no section is written.
+    pub fn is_empty(&self) -> bool {
+        self.forms.is_empty() && self.controls.is_empty()
+    }
+
+    /// Encodes the forms followed by the controls, see [`FormTable`] for the layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut value = Vec::new();
+        for form in &self.forms {
+            value.clear();
+            value.push(form.method as u8);
+            value.extend_from_slice(form.action.as_bytes());
+            write_record(record::FORM, &value, &mut result);
+        }
+        for control in &self.controls {
+            value.clear();
+            value.push(control.kind as u8);
+            value.push(control.checked as u8);
+            write_varint(control.form as u64, &mut value);
+            write_str(&control.name, &mut value);
+            write_str(&control.value, &mut value);
+            for option in &control.options {
+                value.push(option.selected as u8);
+                write_str(&option.value, &mut value);
+                write_str(&option.label, &mut value);
+            }
+            write_record(record::CONTROL, &value, &mut result);
+        }
+        result
+    }
+
+    /// Decodes a form table, dropping records with unknown keys.
+    pub fn decode(table: FormTable<'_>) -> Result<Self> {
+        let mut result = Self::default();
+        for record in table {
+            match record? {
+                FormRecord::Form(form) => result.forms.push(Form {
+                    method: form.method,
+                    action: form.action.to_string(),
+                }),
+                FormRecord::Control(control) => result.controls.push(Control {
+                    kind: control.kind,
+                    form: control.form,
+                    name: control.name.to_string(),
+                    value: control.value.to_string(),
+                    checked: control.checked,
+                    options: control
+                        .options()
+                        .map(|option| SelectOption {
+                            value: option.value.to_string(),
+                            label: option.label.to_string(),
+                            selected: option.selected,
+                        })
+                        .collect(),
+                }),
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// The values a reader submits for a form, for the host to turn into an HTTP request.
+///
+/// Layout:
+/// - method byte, see [`FormMethod`]
+/// - the action URL, as a LEB128 encoded length followed by UTF-8
+/// - the fields until the end, each a name followed by a value in the same way as the action
+///
+/// Which controls send a value is described by [`ControlKind`]. Fields are kept in the order
+/// they were added, which should be the order of the controls in the form table.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SubmissionView<'a> {
+    pub method: FormMethod,
+    pub action: &'a str,
+    fields: &'a [u8],
+}
+
+impl<'a> SubmissionView<'a> {
+    /// Decodes a submission. Errors have the kind [`ErrorKind::InvalidForm`] with key 0.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let mut fields = Fields::new(bytes, 0);
+        let method = fields.byte()?;
+        let method = FormMethod::from_byte(method).ok_or(fields.invalid())?;
+        let action = fields.str()?;
+        let submission = Self {
+            method,
+            action,
+            fields: &bytes[fields.pos..],
+        };
+        // Validate the fields up front, so iterating over them can't fail
+        let start = fields.pos;
+        let mut fields = Fields::new(submission.fields, 0);
+        while !fields.is_empty() {
+            fields.str().and_then(|_| fields.str()).map_err(|e| e.with_offset(start))?;
+        }
+        Ok(submission)
+    }
+
+    /// The names and values of the fields, in the order they were added.
+    pub fn fields(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        let mut fields = Fields::new(self.fields, 0);
+        core::iter::from_fn(move || {
+            if fields.is_empty() {
+                return None;
+            }
+            // Checked by decode
+            Some((fields.str().unwrap(), fields.str().unwrap()))
+        })
+    }
+
+    /// Writes the fields encoded as `application/x-www-form-urlencoded`, see
+    /// [`Submission::query`].
+    pub fn write_query(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        write_query(self.fields(), out)
+    }
+
+    /// Writes the URL to request, see [`Submission::url`].
+    pub fn write_url(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        write_url(self.method, self.action, self.fields(), out)
+    }
+}
+
+/// Encodes a submission into a buffer owned by the caller, for readers without an allocator.
+/// See [`SubmissionView`] for the layout.
+#[derive(Debug)]
+pub struct SubmissionWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SubmissionWriter<'a> {
+    /// Starts a submission of `form` without any fields. Fails with
+    /// [`ErrorKind::ScratchTooSmall`] if `buf` can't hold it.
+    pub fn new(buf: &'a mut [u8], form: &FormView<'_>) -> Result<Self> {
+        let mut writer = Self { buf, len: 0 };
+        writer.put(&[form.method as u8])?;
+        writer.put_str(form.action)?;
+        Ok(writer)
+    }
+
+    /// Adds a field. Fails with [`ErrorKind::ScratchTooSmall`] if the buffer can't hold it, in
+    /// which case nothing is written.
+    pub fn push(&mut self, name: &str, value: &str) -> Result<()> {
+        let len = self.len;
+        let result = self.put_str(name).and_then(|_| self.put_str(value));
+        if result.is_err() {
+            self.len = len;
+        }
+        result
+    }
+
+    /// The encoded submission.
+    pub fn finish(self) -> &'a [u8] {
+        &self.buf[..self.len]
+    }
+
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+        let end = self.len + bytes.len();
+        let out = self
+            .buf
+            .get_mut(self.len..end)
+            .ok_or(crate::Error::new(ErrorKind::ScratchTooSmall { needed: end }))?;
+        out.copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    fn put_str(&mut self, value: &str) -> Result<()> {
+        let mut len = [0; MAX_VARINT_SIZE];
+        let pos = encode_varint(value.len() as u64, &mut len);
+        self.put(&len[..pos])?;
+        self.put(value.as_bytes())
+    }
+}
+
+/// Owned version of [`SubmissionView`], which can be built up field by field.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Submission {
+    pub method: FormMethod,
+    pub action: String,
+    pub fields: Vec<(String, String)>,
+}
+
+#[cfg(feature = "alloc")]
+impl Submission {
+    /// Starts a submission of `form` without any fields.
+    pub fn new(form: &FormView<'_>) -> Self {
+        Self {
+            method: form.method,
+            action: form.action.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, name: &str, value: &str) {
+        self.fields.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = alloc::vec![self.method as u8];
+        write_str(&self.action, &mut result);
+        for (name, value) in &self.fields {
+            write_str(name, &mut result);
+            write_str(value, &mut result);
+        }
+        result
+    }
+
+    /// Decodes a submission. Errors have the kind [`ErrorKind::InvalidForm`] with key 0.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let view = SubmissionView::decode(bytes)?;
+        let mut result = Self {
+            method: view.method,
+            action: view.action.to_string(),
+            fields: Vec::new(),
+        };
+        for (name, value) in view.fields() {
+            result.push(name, value);
+        }
+        Ok(result)
+    }
+
+    fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The fields encoded as `application/x-www-form-urlencoded`, which is the body of a
+    /// [`FormMethod::Post`] request.
+    pub fn query(&self) -> String {
+        let mut result = String::new();
+        // Writing to a string can't fail
+        write_query(self.fields(), &mut result).unwrap();
+        result
+    }
+
+    /// The URL to request, which includes the query for [`FormMethod::Get`].
+    pub fn url(&self) -> String {
+        let mut result = String::new();
+        write_url(self.method, &self.action, self.fields(), &mut result).unwrap();
+        result
+    }
+}
+
+fn write_query<'a>(
+    fields: impl Iterator<Item = (&'a str, &'a str)>,
+    out: &mut impl fmt::Write,
+) -> fmt::Result {
+    for (i, (name, value)) in fields.enumerate() {
+        if i > 0 {
+            out.write_char('&')?;
+        }
+        url_encode(name, out)?;
+        out.write_char('=')?;
+        url_encode(value, out)?;
+    }
+    Ok(())
+}
+
+fn write_url<'a>(
+    method: FormMethod,
+    action: &str,
+    fields: impl Iterator<Item = (&'a str, &'a str)>,
+    out: &mut impl fmt::Write,
+) -> fmt::Result {
+    match method {
+        FormMethod::Get => {
+            // Like browsers, replace any query the action already has but keep its fragment
+            let (url, fragment) = match action.split_once('#') {
+                Some((url, fragment)) => (url, Some(fragment)),
+                None => (action, None),
+            };
+            let base = url.split('?').next().unwrap_or("");
+            out.write_str(base)?;
+            out.write_char('?')?;
+            write_query(fields, out)?;
+            if let Some(fragment) = fragment {
+                out.write_char('#')?;
+                out.write_str(fragment)?;
+            }
+            Ok(())
+        }
+        FormMethod::Post => out.write_str(action),
+    }
+}
+
+/// Percent encodes `value` the way HTML forms do, with spaces as `+`.
+fn url_encode(value: &str, out: &mut impl fmt::Write) -> fmt::Result {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => {
+                out.write_char(byte as char)?
+            }
+            b' ' => out.write_char('+')?,
+            _ => {
+                out.write_char('%')?;
+                out.write_char(HEX[(byte >> 4) as usize] as char)?;
+                out.write_char(HEX[(byte & 0xf) as usize] as char)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_submission() {
+        let form = FormView {
+            method: FormMethod::Get,
+            action: "https://example.com/search?old=1",
+        };
+        let mut submission = Submission::new(&form);
+        submission.push("q", "fish & chips");
+        submission.push("lang", "zoë");
+        let bytes = submission.to_bytes();
+        assert_eq!(Submission::decode(&bytes).unwrap(), submission);
+        assert_eq!(
+            submission.url(),
+            "https://example.com/search?q=fish+%26+chips&lang=zo%C3%AB"
+        );
+
+        let err = Submission::decode(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidForm { key: 0 });
+
+        // Without an allocator, the same submission is written into a buffer
+        let mut buf = [0; 64];
+        let mut writer = SubmissionWriter::new(&mut buf, &form).unwrap();
+        writer.push("q", "fish & chips").unwrap();
+        writer.push("lang", "zoë").unwrap();
+        assert_eq!(writer.finish(), bytes.as_slice());
+        let view = SubmissionView::decode(&bytes).unwrap();
+        let mut url = String::new();
+        view.write_url(&mut url).unwrap();
+        assert_eq!(url, submission.url());
+
+        let mut buf = [0; 56];
+        let mut writer = SubmissionWriter::new(&mut buf, &form).unwrap();
+        writer.push("q", "fish & chips").unwrap();
+        let err = writer.push("lang", "zoë").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ScratchTooSmall { needed: 59 });
+        assert_eq!(writer.finish(), &bytes[..bytes.len() - 10]);
+    }
+
+    #[test]
+    fn test_submission_url_fragment() {
+        // The query of a GET submission goes before the fragment of the action, like in browsers
+        let form = FormView {
+            method: FormMethod::Get,
+            action: "/search?old=1#results",
+        };
+        let mut submission = Submission::new(&form);
+        submission.push("q", "fish");
+        assert_eq!(submission.url(), "/search?q=fish#results");
+
+        let form = FormView {
+            method: FormMethod::Post,
+            ..form
+        };
+        assert_eq!(Submission::new(&form).url(), "/search?old=1#results");
+    }
+}
@@ -0,0 +1,144 @@
+#[cfg(not(feature = "std"))]
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::convert::TryInto;
+
+use crate::{ErrorKind, Result};
+
+/// Magic bytes every .swb file starts with.
+pub const MAGIC: [u8; 4] = *b"SWB\0";
+
+/// Version of the binary format (layout and instruction set) written by this crate.
+/// Readers reject files with a newer version, since they can't know how to interpret them.
+pub const FORMAT_VERSION: u16 = 2;
+
+/// Oldest format version this crate can read. Version 1 had a fixed layout without a
+/// section table.
+pub const MIN_FORMAT_VERSION: u16 = 2;
+
+/// Size of the encoded header in bytes.
+pub const HEADER_SIZE: usize = 12;
+
+/// Feature flags stored in the header. Readers reject files with flags they don't know about.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct Flags(pub u16);
+
+impl Flags {
+    /// The data section is UTF-8 instead of ascii.
+    pub const UTF8_TEXT: Flags = Flags(1 << 0);
+
+    /// The code section uses [`crate::CodeEncoding::Compact`].
+    pub const COMPACT_CODE: Flags = Flags(1 << 1);
+
+    /// The data section is compressed with [`crate::TextCompression::Lzss`].
+    /// The length in the section table is the compressed length.
+    pub const COMPRESSED_TEXT: Flags = Flags(1 << 2);
+
+    /// The header is followed by a CRC-32 of the rest of the file, see [`crate::Crc32`].
+    pub const CHECKSUM: Flags = Flags(1 << 3);
+
+    /// All flags understood by this version of the crate.
+    pub const KNOWN: Flags = Flags(
+        Self::UTF8_TEXT.0 | Self::COMPACT_CODE.0 | Self::COMPRESSED_TEXT.0 | Self::CHECKSUM.0,
+    );
+
+    pub fn contains(&self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn with(self, other: Flags, enabled: bool) -> Self {
+        if enabled {
+            Flags(self.0 | other.0)
+        } else {
+            Flags(self.0 & !other.0)
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.0 & !Self::KNOWN.0 == 0
+    }
+}
+
+/// Layout:
+/// - 4 bytes magic, see [`MAGIC`]
+/// - 2 bytes format version (little endian)
+/// - 2 bytes flags (little endian)
+/// - 4 bytes number of entries in the section table (little endian)
+///
+/// If [`Flags::CHECKSUM`] is set, the header is followed by a little endian CRC-32 over the
+/// header and everything after the checksum. Then follows the section table, see
+/// [`crate::SectionEntry`]. The file must have a [`crate::SectionType::TEXT`] and a
+/// [`crate::SectionType::CODE`] section.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Header {
+    pub version: u16,
+    pub flags: Flags,
+    pub section_count: u32,
+}
+
+impl Header {
+    pub fn new(flags: Flags, section_count: u32) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            flags,
+            section_count,
+        }
+    }
+
+    /// Size of the header, the checksum if there is one, and the section table.
+    pub fn table_end(&self) -> u64 {
+        self.table_start() as u64 + self.section_count as u64 * crate::SECTION_ENTRY_SIZE as u64
+    }
+
+    /// Offset of the section table, which follows the header and the checksum if there is one.
+    pub fn table_start(&self) -> usize {
+        if self.flags.contains(Flags::CHECKSUM) {
+            HEADER_SIZE + crate::CHECKSUM_SIZE
+        } else {
+            HEADER_SIZE
+        }
+    }
+
+    pub fn into_bytes(self) -> [u8; HEADER_SIZE] {
+        let mut result = [0; HEADER_SIZE];
+        result[0..4].copy_from_slice(&MAGIC);
+        result[4..6].copy_from_slice(&self.version.to_le_bytes());
+        result[6..8].copy_from_slice(&self.flags.0.to_le_bytes());
+        result[8..12].copy_from_slice(&self.section_count.to_le_bytes());
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for Header {
+    type Error = crate::Error;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        // Check the magic first, so random short files are reported as such
+        if value.len() < MAGIC.len() || value[0..4] != MAGIC {
+            return Err(crate::Error::at(0, ErrorKind::InvalidMagic));
+        }
+        if value.len() < HEADER_SIZE {
+            return Err(crate::Error::at(
+                0,
+                ErrorKind::Truncated {
+                    needed: HEADER_SIZE,
+                    available: value.len(),
+                },
+            ));
+        }
+        let version = u16::from_le_bytes(value[4..6].try_into().unwrap());
+        if !(MIN_FORMAT_VERSION..=FORMAT_VERSION).contains(&version) {
+            return Err(crate::Error::at(4, ErrorKind::UnsupportedVersion { version }));
+        }
+        let flags = Flags(u16::from_le_bytes(value[6..8].try_into().unwrap()));
+        if !flags.is_known() {
+            return Err(crate::Error::at(6, ErrorKind::UnsupportedFlags { flags: flags.0 }));
+        }
+        let section_count = u32::from_le_bytes(value[8..12].try_into().unwrap());
+        Ok(Self {
+            version,
+            flags,
+            section_count,
+        })
+    }
+}
@@ -0,0 +1,238 @@
+#[cfg(not(feature = "std"))]
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::convert::TryInto;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::section::write_record;
+use crate::section::read_record;
+use crate::{ErrorKind, Result};
+
+/// Formats of entries in an image table.
+pub mod format {
+    /// A [`crate::BitmapView`].
+    pub const MONO: u8 = 1;
+}
+
+/// A 1-bit image. Pixels are stored row by row, 8 to a byte with the most significant bit
+/// first. Every row starts on a new byte, and set bits are black.
+///
+/// Layout:
+/// - 2 bytes width (little endian)
+/// - 2 bytes height (little endian)
+/// - `height` rows of [`BitmapView::stride`] bytes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BitmapView<'a> {
+    pub width: u16,
+    pub height: u16,
+    pub rows: &'a [u8],
+}
+
+/// Number of bytes of a row of `width` pixels.
+pub fn stride(width: u16) -> usize {
+    (width as usize).div_ceil(8)
+}
+
+impl<'a> BitmapView<'a> {
+    /// Decodes and validates a bitmap. Padding bits at the end of rows must be zero.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let invalid = crate::Error::new(ErrorKind::InvalidImage { format: format::MONO });
+        let (width, height) = match bytes.get(0..4) {
+            Some(size) => (
+                u16::from_le_bytes(size[0..2].try_into().unwrap()),
+                u16::from_le_bytes(size[2..4].try_into().unwrap()),
+            ),
+            None => return Err(invalid),
+        };
+        let rows = &bytes[4..];
+        let stride = stride(width);
+        if rows.len() != stride * height as usize {
+            return Err(invalid);
+        }
+        let padding = (stride * 8 - width as usize) as u32;
+        if padding > 0 && rows.chunks(stride).any(|row| row[stride - 1].trailing_zeros() < padding) {
+            return Err(invalid);
+        }
+        Ok(Self { width, height, rows })
+    }
+
+    pub fn stride(&self) -> usize {
+        stride(self.width)
+    }
+
+    /// Whether the pixel at `x`, `y` is black. Panics if it's outside of the image.
+    pub fn pixel(&self, x: u16, y: u16) -> bool {
+        assert!(x < self.width && y < self.height);
+        let byte = self.rows[y as usize * self.stride() + x as usize / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+}
+
+/// An image, borrowed from an encoded [`crate::SectionType::IMAGES`] section.
+///
+/// The section is a list of records, one per image in the order of their index. Each record is
+/// a format byte (see [`format`]), the LEB128 encoded length of the image and the image itself.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ImageView<'a> {
+    Mono(BitmapView<'a>),
+    /// An image format this reader doesn't know, which can't be shown.
+    Unknown { format: u8, data: &'a [u8] },
+}
+
+/// Iterates over the images of an encoded image table. Iteration stops after the first error.
+#[derive(Debug, Clone)]
+pub struct ImageTable<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ImageTable<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Returns the image with the given index, as used by [`crate::Instruction::Image`].
+    pub fn get(&self, index: u32) -> Option<Result<ImageView<'a>>> {
+        self.clone().nth(index as usize)
+    }
+
+    fn decode_next(&mut self) -> Result<ImageView<'a>> {
+        let (format, data, len) = read_record(&self.bytes[self.pos..])?;
+        let image = match format {
+            format::MONO => ImageView::Mono(BitmapView::decode(data)?),
+            format => ImageView::Unknown { format, data },
+        };
+        self.pos += len;
+        Ok(image)
+    }
+}
+
+impl<'a> Iterator for ImageTable<'a> {
+    type Item = Result<ImageView<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.bytes.len() {
+            return None;
+        }
+        let result = self.decode_next().map_err(|e| e.with_offset(self.pos));
+        if result.is_err() {
+            self.pos = self.bytes.len();
+        }
+        Some(result)
+    }
+}
+
+/// Owned version of [`BitmapView`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Bitmap {
+    pub width: u16,
+    pub height: u16,
+    pub rows: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl Bitmap {
+    /// Creates an all white bitmap.
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            rows: alloc::vec![0; stride(width) * height as usize],
+        }
+    }
+
+    pub fn view(&self) -> BitmapView<'_> {
+        BitmapView {
+            width: self.width,
+            height: self.height,
+            rows: &self.rows,
+        }
+    }
+
+    /// Makes the pixel at `x`, `y` black or white. Panics if it's outside of the image.
+    pub fn set_pixel(&mut self, x: u16, y: u16, black: bool) {
+        assert!(x < self.width && y < self.height);
+        let byte = &mut self.rows[y as usize * stride(self.width) + x as usize / 8];
+        if black {
+            *byte |= 0x80 >> (x % 8);
+        } else {
+            *byte &= !(0x80 >> (x % 8));
+        }
+    }
+}
+
+/// Owned version of [`ImageView`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Image {
+    Mono(Bitmap),
+    Unknown { format: u8, data: Vec<u8> },
+}
+
+#[cfg(feature = "alloc")]
+impl Image {
+    /// Appends the record for this image to an encoded image table.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Image::Mono(bitmap) => {
+                let mut data = Vec::with_capacity(4 + bitmap.rows.len());
+                data.extend_from_slice(&bitmap.width.to_le_bytes());
+                data.extend_from_slice(&bitmap.height.to_le_bytes());
+                data.extend_from_slice(&bitmap.rows);
+                write_record(format::MONO, &data, out);
+            }
+            Image::Unknown { format, data } => write_record(*format, data, out),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<ImageView<'a>> for Image {
+    fn from(view: ImageView<'a>) -> Self {
+        match view {
+            ImageView::Mono(bitmap) => Image::Mono(Bitmap {
+                width: bitmap.width,
+                height: bitmap.height,
+                rows: bitmap.rows.to_vec(),
+            }),
+            ImageView::Unknown { format, data } => Image::Unknown {
+                format,
+                data: data.to_vec(),
+            },
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    #[test]
+    fn test_bitmap() {
+        let mut bitmap = Bitmap::new(10, 2);
+        bitmap.set_pixel(0, 0, true);
+        bitmap.set_pixel(9, 1, true);
+        assert_eq!(bitmap.rows, vec![0x80, 0x00, 0x00, 0x40]);
+
+        let mut table = Vec::new();
+        Image::Mono(bitmap.clone()).encode(&mut table);
+        let view = ImageTable::new(&table).get(0).unwrap().unwrap();
+        assert_eq!(view, ImageView::Mono(bitmap.view()));
+        let ImageView::Mono(view) = view else { unreachable!() };
+        assert!(view.pixel(9, 1) && !view.pixel(8, 1));
+
+        // Set padding bits are rejected
+        let mut padded = bitmap;
+        padded.rows[1] = 0x01;
+        let mut table = Vec::new();
+        Image::Mono(padded).encode(&mut table);
+        let err = ImageTable::new(&table).get(0).unwrap().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidImage { format: format::MONO });
+    }
+}
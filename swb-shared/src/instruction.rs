@@ -9,14 +9,89 @@ use std::convert::TryInto;
 use core::convert::TryInto;
 
 use crate::address::AddressRange;
-use crate::Result;
+use crate::{ControlKind, ErrorKind, Result};
 use crate::Address;
 
+/// A text style, set by a push and unset by the matching pop. Readers that can't show a style
+/// should still show the text, so skipping styles they don't support is always safe.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-#[repr(u8)]
 pub enum StyleVar {
-    Bold = 1,
-    Italic = 2,
+    Bold,
+    Italic,
+    Underline,
+    /// Fixed-width text, like code.
+    Monospace,
+    Strikethrough,
+    Superscript,
+    Subscript,
+    /// Text smaller than the surrounding text.
+    Small,
+    /// Text larger than the surrounding text.
+    Large,
+    /// A style added in a later version, which this reader should ignore. Always 10 or more,
+    /// lower values are the styles above.
+    Unknown(UnknownValue),
+}
+
+/// The value of a style or other operand added in a later version. These only come from
+/// decoding, so they never hold the value of a variant this reader knows.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UnknownValue(u8);
+
+impl UnknownValue {
+    pub fn get(self) -> u8 {
+        self.0
+    }
+}
+
+/// What a list is numbered with.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ListKind {
+    /// Items are marked with bullets.
+    Unordered,
+    /// Items are numbered, starting at the given number.
+    Ordered(u32),
+}
+
+/// How a horizontal rule is drawn.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RuleStyle {
+    Solid,
+    Dashed,
+    Dotted,
+    /// A style added in a later version, which this reader should draw as solid. Always 3 or
+    /// more, lower values are the styles above.
+    Unknown(UnknownValue),
+}
+
+/// How lines of text are aligned.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    /// Stretches every line but the last of a paragraph to the full width.
+    Justify,
+    /// An alignment added in a later version, which this reader should treat as left. Always 4
+    /// or more, lower values are the alignments above.
+    Unknown(UnknownValue),
+}
+
+/// A horizontal rule, drawn as a line across the page.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Rule {
+    /// Thickness in pixels, or 0 to let the reader decide.
+    pub thickness: u8,
+    pub style: RuleStyle,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            thickness: 0,
+            style: RuleStyle::Solid,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -27,6 +102,58 @@ pub enum Instruction {
     Push(StyleVar) = 2,
     Pop(StyleVar) = 3,
     Endl = 4,
+    /// Starts a link, the argument is its index in the link table, see [`crate::LinkTable`].
+    LinkStart(u32) = 5,
+    LinkEnd = 6,
+    /// Starts a heading, the argument is its level from 1 (most important) to 6.
+    PushHeading(u8) = 7,
+    PopHeading(u8) = 8,
+    /// Starts a list, lists can be nested.
+    ListStart(ListKind) = 9,
+    ListEnd = 10,
+    /// Starts the next item of the innermost list.
+    ListItem = 11,
+    /// Starts a table, tables can be nested.
+    TableStart = 12,
+    TableEnd = 13,
+    /// Starts the next row of the innermost table.
+    TableRow = 14,
+    /// Starts the next cell of the current row.
+    TableCell = 15,
+    /// Starts the next cell of the current row, which is a header for its row or column.
+    TableHeaderCell = 16,
+    /// Shows an image, the argument is its index in the image table, see [`crate::ImageTable`].
+    Image(u32) = 17,
+    /// Shows a text box, the argument is its index among the controls in the form table,
+    /// see [`crate::FormTable`]. The same goes for the other form controls.
+    TextInput(u32) = 18,
+    Checkbox(u32) = 19,
+    Radio(u32) = 20,
+    Select(u32) = 21,
+    /// Shows a button that submits the form of the control.
+    Submit(u32) = 22,
+    /// Marks a position that links can jump to, the argument is its index in the anchor table,
+    /// see [`crate::AnchorTable`].
+    Anchor(u32) = 23,
+    /// Ends the current line if anything was written on it. Unlike `Endl`, several in a row
+    /// don't add empty lines.
+    BlockBreak = 24,
+    /// Starts a paragraph, which renderers set apart with some vertical space.
+    ParagraphStart = 25,
+    ParagraphEnd = 26,
+    /// Indents the lines that follow by the given number of levels, starting a new line like
+    /// `BlockBreak`. The level is absolute, so 0 ends all indentation.
+    Indent(u8) = 27,
+    /// Draws a horizontal rule on a line of its own.
+    Rule(Rule) = 28,
+    /// Starts preformatted text, which is laid out exactly as it is: spaces are kept, lines only
+    /// end at `Endl` and aren't wrapped. Readers clip or scroll lines that don't fit.
+    PreformattedStart = 29,
+    PreformattedEnd = 30,
+    /// Aligns the lines that follow, starting a new line like `BlockBreak`. Alignments can be
+    /// nested, `PopAlign` goes back to the previous one.
+    PushAlign(Align) = 31,
+    PopAlign(Align) = 32,
 }
 
 impl Instruction {
@@ -36,10 +163,29 @@ impl Instruction {
         // see https://doc.rust-lang.org/std/mem/fn.discriminant.html#accessing-the-numeric-value-of-the-discriminant
         unsafe { *<*const _>::from(self).cast::<u8>() }
     }
+
+    /// The kind and index of the control a form control instruction shows.
+    pub fn control(&self) -> Option<(ControlKind, u32)> {
+        match *self {
+            Instruction::TextInput(index) => Some((ControlKind::TextInput, index)),
+            Instruction::Checkbox(index) => Some((ControlKind::Checkbox, index)),
+            Instruction::Radio(index) => Some((ControlKind::Radio, index)),
+            Instruction::Select(index) => Some((ControlKind::Select, index)),
+            Instruction::Submit(index) => Some((ControlKind::Submit, index)),
+            _ => None,
+        }
+    }
 }
 
 /// - Text: lower 32 bits of the argument are the base address, upper 32 bits are the range
 /// - Push/Pop: lower 8 bits of the argument are the style var, upper 56 bits are zero.
+/// - LinkStart/Image/form controls/Anchor: lower 32 bits of the argument are the index, upper 32 bits are zero.
+/// - PushHeading/PopHeading: lower 8 bits of the argument are the level, upper 56 bits are zero.
+/// - Indent: lower 8 bits of the argument are the level, upper 56 bits are zero.
+/// - Rule: lower 8 bits of the argument are the thickness, the next 8 bits are the style
+///   (0 for solid, 1 for dashed, 2 for dotted). The remaining bits are zero.
+/// - ListStart: lower 8 bits of the argument are the kind (0 for unordered, 1 for ordered),
+///   the next 32 bits are the start number of ordered lists. The remaining bits are zero.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(C)]
 pub struct BinaryInstruction {
@@ -48,6 +194,9 @@ pub struct BinaryInstruction {
 }
 
 impl BinaryInstruction {
+    /// Size of an encoded instruction in bytes
+    pub const ENCODED_SIZE: usize = 9;
+
     pub fn into_bytes(self) -> [u8; 9] {
         let mut result: [u8; 9] = [self.ty, 0, 0, 0, 0, 0, 0, 0, 0];
         result[1..].copy_from_slice(&self.arg.to_le_bytes());
@@ -68,6 +217,26 @@ impl TryFrom<[u8; 9]> for BinaryInstruction {
     }
 }
 
+/// Shape of the argument of an instruction type, used by the compact encoding.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Operand {
+    None,
+    TextRange,
+    Value,
+}
+
+impl Operand {
+    /// Returns `None` for unknown instruction types.
+    pub(crate) fn of(ty: u8) -> Option<Operand> {
+        match ty {
+            0 | 4 | 6 | 10..=16 | 24..=26 | 29 | 30 => Some(Operand::None),
+            1 => Some(Operand::TextRange),
+            2 | 3 | 5 | 7 | 8 | 9 | 17..=23 | 27 | 28 | 31 | 32 => Some(Operand::Value),
+            _ => None,
+        }
+    }
+}
+
 pub trait ToBinary {
     type Output;
     fn to_binary(&self) -> Self::Output;
@@ -77,15 +246,26 @@ impl ToBinary for StyleVar {
     type Output = u8;
 
     fn to_binary(&self) -> Self::Output {
-        *self as Self::Output
+        match self {
+            StyleVar::Bold => 1,
+            StyleVar::Italic => 2,
+            StyleVar::Underline => 3,
+            StyleVar::Monospace => 4,
+            StyleVar::Strikethrough => 5,
+            StyleVar::Superscript => 6,
+            StyleVar::Subscript => 7,
+            StyleVar::Small => 8,
+            StyleVar::Large => 9,
+            StyleVar::Unknown(value) => value.get(),
+        }
     }
 }
 
-fn pack_u32_into_u64(lower: u32, upper: u32) -> u64 {
+pub(crate) fn pack_u32_into_u64(lower: u32, upper: u32) -> u64 {
     lower as u64 | ((upper as u64) << 32)
 }
 
-fn unpack_u64(value: u64) -> (u32, u32) {
+pub(crate) fn unpack_u64(value: u64) -> (u32, u32) {
     let higher = (value >> 32) as u32;
     let lower = (value & 0xffffffff) as u32;
     (higher, lower)
@@ -99,12 +279,80 @@ fn parse_address_range(value: u64) -> AddressRange {
     }
 }
 
-fn parse_style_var(value: u64) -> Result<StyleVar> {
+pub(crate) fn parse_style_var(value: u64) -> Result<StyleVar> {
     match value {
         1 => Ok(StyleVar::Bold),
         2 => Ok(StyleVar::Italic),
-        _ => Err(crate::Error("Invalid style var encoding"))
+        3 => Ok(StyleVar::Underline),
+        4 => Ok(StyleVar::Monospace),
+        5 => Ok(StyleVar::Strikethrough),
+        6 => Ok(StyleVar::Superscript),
+        7 => Ok(StyleVar::Subscript),
+        8 => Ok(StyleVar::Small),
+        9 => Ok(StyleVar::Large),
+        // Newer styles still fit in the lower 8 bits
+        10..=0xff => Ok(StyleVar::Unknown(UnknownValue(value as u8))),
+        _ => Err(crate::Error::new(ErrorKind::InvalidStyleVar { value }))
+    }
+}
+
+/// Highest heading level, like `<h6>`.
+pub const MAX_HEADING_LEVEL: u8 = 6;
+
+fn parse_heading_level(value: u64) -> Result<u8> {
+    if (1..=MAX_HEADING_LEVEL as u64).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(crate::Error::new(ErrorKind::InvalidHeadingLevel { level: value }))
+    }
+}
+
+fn parse_list_kind(value: u64, opcode: u8) -> Result<ListKind> {
+    match (value & 0xff, value >> 8) {
+        (0, 0) => Ok(ListKind::Unordered),
+        (1, start) if start <= u32::MAX as u64 => Ok(ListKind::Ordered(start as u32)),
+        _ => Err(crate::Error::new(ErrorKind::InvalidOperand { opcode })),
+    }
+}
+
+fn parse_rule(value: u64, opcode: u8) -> Result<Rule> {
+    if value >> 16 != 0 {
+        return Err(crate::Error::new(ErrorKind::InvalidOperand { opcode }));
     }
+    Ok(Rule {
+        thickness: value as u8,
+        style: parse_rule_style((value >> 8) as u8),
+    })
+}
+
+pub(crate) fn parse_rule_style(value: u8) -> RuleStyle {
+    match value {
+        0 => RuleStyle::Solid,
+        1 => RuleStyle::Dashed,
+        2 => RuleStyle::Dotted,
+        style => RuleStyle::Unknown(UnknownValue(style)),
+    }
+}
+
+fn parse_align(value: u64, opcode: u8) -> Result<Align> {
+    match u8::try_from(value) {
+        Ok(value) => Ok(parse_align_value(value)),
+        Err(_) => Err(crate::Error::new(ErrorKind::InvalidOperand { opcode })),
+    }
+}
+
+pub(crate) fn parse_align_value(value: u8) -> Align {
+    match value {
+        0 => Align::Left,
+        1 => Align::Center,
+        2 => Align::Right,
+        3 => Align::Justify,
+        align => Align::Unknown(UnknownValue(align)),
+    }
+}
+
+fn parse_index(value: u64, opcode: u8) -> Result<u32> {
+    u32::try_from(value).map_err(|_| crate::Error::new(ErrorKind::InvalidOperand { opcode }))
 }
 
 impl TryFrom<BinaryInstruction> for Instruction {
@@ -117,7 +365,38 @@ impl TryFrom<BinaryInstruction> for Instruction {
             2 => Ok(Instruction::Push(parse_style_var(value.arg)?)),
             3 => Ok(Instruction::Pop(parse_style_var(value.arg)?)),
             4 => Ok(Instruction::Endl),
-            _ => Err(crate::Error("Invalid instruction type")),
+            5 => Ok(Instruction::LinkStart(parse_index(value.arg, value.ty)?)),
+            6 => Ok(Instruction::LinkEnd),
+            7 => Ok(Instruction::PushHeading(parse_heading_level(value.arg)?)),
+            8 => Ok(Instruction::PopHeading(parse_heading_level(value.arg)?)),
+            9 => Ok(Instruction::ListStart(parse_list_kind(value.arg, value.ty)?)),
+            10 => Ok(Instruction::ListEnd),
+            11 => Ok(Instruction::ListItem),
+            12 => Ok(Instruction::TableStart),
+            13 => Ok(Instruction::TableEnd),
+            14 => Ok(Instruction::TableRow),
+            15 => Ok(Instruction::TableCell),
+            16 => Ok(Instruction::TableHeaderCell),
+            17 => Ok(Instruction::Image(parse_index(value.arg, value.ty)?)),
+            18 => Ok(Instruction::TextInput(parse_index(value.arg, value.ty)?)),
+            19 => Ok(Instruction::Checkbox(parse_index(value.arg, value.ty)?)),
+            20 => Ok(Instruction::Radio(parse_index(value.arg, value.ty)?)),
+            21 => Ok(Instruction::Select(parse_index(value.arg, value.ty)?)),
+            22 => Ok(Instruction::Submit(parse_index(value.arg, value.ty)?)),
+            23 => Ok(Instruction::Anchor(parse_index(value.arg, value.ty)?)),
+            24 => Ok(Instruction::BlockBreak),
+            25 => Ok(Instruction::ParagraphStart),
+            26 => Ok(Instruction::ParagraphEnd),
+            27 => Ok(Instruction::Indent(
+                u8::try_from(value.arg)
+                    .map_err(|_| crate::Error::new(ErrorKind::InvalidOperand { opcode: value.ty }))?,
+            )),
+            28 => Ok(Instruction::Rule(parse_rule(value.arg, value.ty)?)),
+            29 => Ok(Instruction::PreformattedStart),
+            30 => Ok(Instruction::PreformattedEnd),
+            31 => Ok(Instruction::PushAlign(parse_align(value.arg, value.ty)?)),
+            32 => Ok(Instruction::PopAlign(parse_align(value.arg, value.ty)?)),
+            opcode => Err(crate::Error::new(ErrorKind::InvalidOpcode { opcode })),
         }?;
         Ok(instruction)
     }
@@ -138,20 +417,89 @@ impl ToBinary for Instruction {
                 }
             },
             Instruction::Push(style) => {
-                let arg = *style as u8 as u64;
+                let arg = style.to_binary() as u64;
                 BinaryInstruction {
                     ty,
                     arg,
                 }
             }
             Instruction::Pop(style) => {
-                let arg = *style as u8 as u64;
+                let arg = style.to_binary() as u64;
                 BinaryInstruction {
                     ty,
                     arg,
                 }
             }
-            Instruction::Endl => {
+            Instruction::LinkStart(index)
+            | Instruction::Image(index)
+            | Instruction::TextInput(index)
+            | Instruction::Checkbox(index)
+            | Instruction::Radio(index)
+            | Instruction::Select(index)
+            | Instruction::Submit(index)
+            | Instruction::Anchor(index) => {
+                BinaryInstruction {
+                    ty,
+                    arg: *index as u64,
+                }
+            }
+            Instruction::PushHeading(level)
+            | Instruction::PopHeading(level)
+            | Instruction::Indent(level) => {
+                BinaryInstruction {
+                    ty,
+                    arg: *level as u64,
+                }
+            }
+            Instruction::ListStart(kind) => {
+                let arg = match kind {
+                    ListKind::Unordered => 0,
+                    ListKind::Ordered(start) => 1 | (*start as u64) << 8,
+                };
+                BinaryInstruction {
+                    ty,
+                    arg,
+                }
+            }
+            Instruction::Rule(rule) => {
+                let style = match rule.style {
+                    RuleStyle::Solid => 0,
+                    RuleStyle::Dashed => 1,
+                    RuleStyle::Dotted => 2,
+                    RuleStyle::Unknown(style) => style.get(),
+                };
+                BinaryInstruction {
+                    ty,
+                    arg: rule.thickness as u64 | (style as u64) << 8,
+                }
+            }
+            Instruction::PushAlign(align) | Instruction::PopAlign(align) => {
+                let arg = match align {
+                    Align::Left => 0,
+                    Align::Center => 1,
+                    Align::Right => 2,
+                    Align::Justify => 3,
+                    Align::Unknown(align) => align.get(),
+                };
+                BinaryInstruction {
+                    ty,
+                    arg: arg as u64,
+                }
+            }
+            Instruction::Endl
+            | Instruction::LinkEnd
+            | Instruction::ListEnd
+            | Instruction::ListItem
+            | Instruction::TableStart
+            | Instruction::TableEnd
+            | Instruction::TableRow
+            | Instruction::TableCell
+            | Instruction::TableHeaderCell
+            | Instruction::BlockBreak
+            | Instruction::ParagraphStart
+            | Instruction::ParagraphEnd
+            | Instruction::PreformattedStart
+            | Instruction::PreformattedEnd => {
                 BinaryInstruction {
                     ty,
                     arg: 0
@@ -173,11 +521,42 @@ impl fmt::Display for StyleVar {
         match self {
             StyleVar::Bold => { write!(f, "bold")?; }
             StyleVar::Italic => { write!(f, "italic")?; }
+            StyleVar::Underline => { write!(f, "underline")?; }
+            StyleVar::Monospace => { write!(f, "monospace")?; }
+            StyleVar::Strikethrough => { write!(f, "strikethrough")?; }
+            StyleVar::Superscript => { write!(f, "superscript")?; }
+            StyleVar::Subscript => { write!(f, "subscript")?; }
+            StyleVar::Small => { write!(f, "small")?; }
+            StyleVar::Large => { write!(f, "large")?; }
+            StyleVar::Unknown(value) => { write!(f, "style {}", value.get())?; }
         };
         Ok(())
     }
 }
 
+impl fmt::Display for Align {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Align::Left => write!(f, "left"),
+            Align::Center => write!(f, "center"),
+            Align::Right => write!(f, "right"),
+            Align::Justify => write!(f, "justify"),
+            Align::Unknown(align) => write!(f, "align {}", align.get()),
+        }
+    }
+}
+
+impl fmt::Display for RuleStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleStyle::Solid => write!(f, "solid"),
+            RuleStyle::Dashed => write!(f, "dashed"),
+            RuleStyle::Dotted => write!(f, "dotted"),
+            RuleStyle::Unknown(style) => write!(f, "style {}", style.get()),
+        }
+    }
+}
+
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -196,6 +575,87 @@ impl fmt::Display for Instruction {
             Instruction::Endl => {
                 write!(f, "endl")?;
             }
+            Instruction::LinkStart(index) => {
+                write!(f, "link {index}")?;
+            }
+            Instruction::LinkEnd => {
+                write!(f, "endlink")?;
+            }
+            Instruction::PushHeading(level) => {
+                write!(f, "push h{level}")?;
+            }
+            Instruction::PopHeading(level) => {
+                write!(f, "pop h{level}")?;
+            }
+            Instruction::ListStart(ListKind::Unordered) => {
+                write!(f, "list unordered")?;
+            }
+            Instruction::ListStart(ListKind::Ordered(start)) => {
+                write!(f, "list ordered {start}")?;
+            }
+            Instruction::ListEnd => {
+                write!(f, "endlist")?;
+            }
+            Instruction::ListItem => {
+                write!(f, "item")?;
+            }
+            Instruction::TableStart => {
+                write!(f, "table")?;
+            }
+            Instruction::TableEnd => {
+                write!(f, "endtable")?;
+            }
+            Instruction::TableRow => {
+                write!(f, "row")?;
+            }
+            Instruction::TableCell => {
+                write!(f, "cell")?;
+            }
+            Instruction::TableHeaderCell => {
+                write!(f, "headercell")?;
+            }
+            Instruction::Image(index) => {
+                write!(f, "image {index}")?;
+            }
+            Instruction::TextInput(index)
+            | Instruction::Checkbox(index)
+            | Instruction::Radio(index)
+            | Instruction::Select(index)
+            | Instruction::Submit(index) => {
+                // Named after the control kind, like `checkbox 3`
+                let (kind, _) = self.control().unwrap();
+                write!(f, "{} {index}", kind.as_str())?;
+            }
+            Instruction::Anchor(index) => {
+                write!(f, "anchor {index}")?;
+            }
+            Instruction::BlockBreak => {
+                write!(f, "break")?;
+            }
+            Instruction::ParagraphStart => {
+                write!(f, "paragraph")?;
+            }
+            Instruction::ParagraphEnd => {
+                write!(f, "endparagraph")?;
+            }
+            Instruction::Indent(level) => {
+                write!(f, "indent {level}")?;
+            }
+            Instruction::Rule(rule) => {
+                write!(f, "rule {} {}", rule.thickness, rule.style)?;
+            }
+            Instruction::PreformattedStart => {
+                write!(f, "pre")?;
+            }
+            Instruction::PreformattedEnd => {
+                write!(f, "endpre")?;
+            }
+            Instruction::PushAlign(align) => {
+                write!(f, "push {align}")?;
+            }
+            Instruction::PopAlign(align) => {
+                write!(f, "pop {align}")?;
+            }
         };
 
         Ok(())
@@ -2,15 +2,63 @@
 
 #![feature(error_in_core)]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 extern crate core;
 
 pub mod instruction;
 pub mod address;
+#[cfg(feature = "alloc")]
 pub mod program;
 pub mod error;
+pub mod header;
+#[cfg(feature = "alloc")]
+pub mod text;
+pub mod compact;
+pub mod compression;
+pub mod view;
+pub mod stream;
+pub mod checksum;
+pub mod section;
+pub mod meta;
+pub mod link;
+pub mod image;
+pub mod form;
+pub mod anchor;
+#[cfg(feature = "std")]
+pub mod asm;
 
 pub use instruction::*;
 pub use address::*;
+#[cfg(feature = "alloc")]
 pub use program::*;
 pub use error::*;
+pub use header::*;
+#[cfg(feature = "alloc")]
+pub use text::*;
+pub use compact::*;
+pub use compression::TextCompression;
+pub use view::*;
+pub use stream::*;
+pub use checksum::*;
+pub use section::*;
+pub use meta::MetadataView;
+#[cfg(feature = "alloc")]
+pub use meta::Metadata;
+pub use link::{LinkTable, LinkTarget};
+#[cfg(feature = "alloc")]
+pub use link::Link;
+pub use image::{BitmapView, ImageTable, ImageView};
+#[cfg(feature = "alloc")]
+pub use image::{Bitmap, Image};
+pub use form::{
+    ControlKind, ControlView, FormMethod, FormRecord, FormTable, FormView, SelectOptionView, SubmissionView,
+    SubmissionWriter,
+};
+#[cfg(feature = "alloc")]
+pub use form::{Control, Form, Forms, SelectOption, Submission};
+pub use anchor::{AnchorTable, AnchorView};
+#[cfg(feature = "alloc")]
+pub use anchor::Anchor;
+#[cfg(feature = "std")]
+pub use asm::{assemble, AsmError};
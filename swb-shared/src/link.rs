@@ -0,0 +1,128 @@
+#[cfg(not(feature = "std"))]
+use core::str;
+#[cfg(feature = "std")]
+use std::str;
+
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::section::write_record;
+#[cfg(feature = "alloc")]
+use crate::write_varint;
+use crate::section::read_record;
+use crate::{read_varint, ErrorKind, Result};
+
+/// Kinds of entries in a link table.
+pub mod kind {
+    /// The target is a URL, stored as UTF-8.
+    pub const URL: u8 = 1;
+    /// The target is an instruction in the same program, stored as its LEB128 encoded index.
+    pub const JUMP: u8 = 2;
+}
+
+/// Where a link points, borrowed from an encoded [`crate::SectionType::LINKS`] section.
+///
+/// The section is a list of records, one per link in the order of their index. Each record is
+/// a kind byte (see [`kind`]), the LEB128 encoded length of the target and the target itself.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LinkTarget<'a> {
+    Url(&'a str),
+    /// Index of the instruction to continue reading at, usually an
+    /// [`crate::Instruction::Anchor`].
+    Jump(u32),
+    /// A kind of target this reader doesn't know, which can't be followed.
+    Unknown { kind: u8, data: &'a [u8] },
+}
+
+/// Iterates over the targets of an encoded link table. Iteration stops after the first error.
+#[derive(Debug, Clone)]
+pub struct LinkTable<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> LinkTable<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Returns the target of the link with the given index, as used by
+    /// [`crate::Instruction::LinkStart`].
+    pub fn get(&self, index: u32) -> Option<Result<LinkTarget<'a>>> {
+        self.clone().nth(index as usize)
+    }
+
+    fn decode_next(&mut self) -> Result<LinkTarget<'a>> {
+        let (kind, data, len) = read_record(&self.bytes[self.pos..])?;
+        let invalid = || crate::Error::new(ErrorKind::InvalidLinkTarget { kind });
+        let target = match kind {
+            kind::URL => LinkTarget::Url(str::from_utf8(data).map_err(|_| invalid())?),
+            kind::JUMP => match read_varint(data) {
+                Ok((index, index_len)) if index_len == data.len() => {
+                    LinkTarget::Jump(u32::try_from(index).map_err(|_| invalid())?)
+                }
+                _ => return Err(invalid()),
+            },
+            kind => LinkTarget::Unknown { kind, data },
+        };
+        self.pos += len;
+        Ok(target)
+    }
+}
+
+impl<'a> Iterator for LinkTable<'a> {
+    type Item = Result<LinkTarget<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.bytes.len() {
+            return None;
+        }
+        let result = self.decode_next().map_err(|e| e.with_offset(self.pos));
+        if result.is_err() {
+            self.pos = self.bytes.len();
+        }
+        Some(result)
+    }
+}
+
+/// Owned version of [`LinkTarget`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Link {
+    Url(String),
+    Jump(u32),
+    Unknown { kind: u8, data: Vec<u8> },
+}
+
+#[cfg(feature = "alloc")]
+impl Link {
+    /// Appends the record for this link to an encoded link table.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Link::Url(url) => write_record(kind::URL, url.as_bytes(), out),
+            Link::Jump(index) => {
+                let mut data = Vec::new();
+                write_varint(*index as u64, &mut data);
+                write_record(kind::JUMP, &data, out);
+            }
+            Link::Unknown { kind, data } => write_record(*kind, data, out),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<LinkTarget<'a>> for Link {
+    fn from(target: LinkTarget<'a>) -> Self {
+        match target {
+            LinkTarget::Url(url) => Link::Url(url.to_string()),
+            LinkTarget::Jump(index) => Link::Jump(index),
+            LinkTarget::Unknown { kind, data } => Link::Unknown {
+                kind,
+                data: data.to_vec(),
+            },
+        }
+    }
+}
@@ -0,0 +1,154 @@
+#[cfg(not(feature = "std"))]
+use core::str;
+#[cfg(feature = "std")]
+use std::str;
+
+#[cfg(not(feature = "std"))]
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::convert::TryInto;
+
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::section::write_record;
+use crate::section::read_record;
+use crate::{ErrorKind, Result};
+
+/// Keys of the records in a metadata section.
+pub mod key {
+    pub const TITLE: u8 = 1;
+    pub const URL: u8 = 2;
+    pub const LANG: u8 = 3;
+    pub const TIMESTAMP: u8 = 4;
+    pub const COMPILER: u8 = 5;
+}
+
+/// Information about the page a program was compiled from, borrowed from an encoded
+/// [`crate::SectionType::META`] section.
+///
+/// The section is a list of records, each a key byte (see [`key`]), the LEB128 encoded length
+/// of the value and the value itself. Text values are UTF-8, the timestamp is a little endian
+/// u64 holding seconds since the unix epoch. Records with unknown keys are skipped.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct MetadataView<'a> {
+    pub title: Option<&'a str>,
+    /// Where the page was fetched from
+    pub url: Option<&'a str>,
+    /// Language of the page, as a BCP 47 tag like `en-GB`
+    pub lang: Option<&'a str>,
+    /// When the page was compiled, in seconds since the unix epoch
+    pub timestamp: Option<u64>,
+    /// Name and version of the compiler that produced the program
+    pub compiler: Option<&'a str>,
+}
+
+impl<'a> MetadataView<'a> {
+    /// Decodes a metadata section. Offsets in errors are relative to the start of `bytes`.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let mut result = Self::default();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let (key, value, len) = read_record(&bytes[pos..]).map_err(|e| e.with_offset(pos))?;
+            let invalid = crate::Error::at(pos, ErrorKind::InvalidMetadata { key });
+            let text = || str::from_utf8(value).map_err(|_| invalid);
+            match key {
+                key::TITLE => result.title = Some(text()?),
+                key::URL => result.url = Some(text()?),
+                key::LANG => result.lang = Some(text()?),
+                key::TIMESTAMP => {
+                    let value: [u8; 8] = value.try_into().map_err(|_| invalid)?;
+                    result.timestamp = Some(u64::from_le_bytes(value));
+                }
+                key::COMPILER => result.compiler = Some(text()?),
+                _ => {}
+            }
+            pos += len;
+        }
+        Ok(result)
+    }
+}
+
+/// Owned version of [`MetadataView`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    /// Where the page was fetched from
+    pub url: Option<String>,
+    /// Language of the page, as a BCP 47 tag like `en-GB`
+    pub lang: Option<String>,
+    /// When the page was compiled, in seconds since the unix epoch
+    pub timestamp: Option<u64>,
+    /// Name and version of the compiler that produced the program
+    pub compiler: Option<String>,
+}
+
+#[cfg(feature = "alloc")]
+impl Metadata {
+    /// Whether none of the fields are set, in which case no section is written.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Encodes the fields that are set, see [`MetadataView`] for the layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut record = |key: u8, value: &[u8]| write_record(key, value, &mut result);
+        let texts = [
+            (key::TITLE, &self.title),
+            (key::URL, &self.url),
+            (key::LANG, &self.lang),
+        ];
+        for (key, value) in texts {
+            if let Some(value) = value {
+                record(key, value.as_bytes());
+            }
+        }
+        if let Some(timestamp) = self.timestamp {
+            record(key::TIMESTAMP, &timestamp.to_le_bytes());
+        }
+        if let Some(compiler) = &self.compiler {
+            record(key::COMPILER, compiler.as_bytes());
+        }
+        result
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<MetadataView<'a>> for Metadata {
+    fn from(view: MetadataView<'a>) -> Self {
+        Self {
+            title: view.title.map(ToString::to_string),
+            url: view.url.map(ToString::to_string),
+            lang: view.lang.map(ToString::to_string),
+            timestamp: view.timestamp,
+            compiler: view.compiler.map(ToString::to_string),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let meta = Metadata {
+            title: Some(String::from("Zoë's page")),
+            lang: Some(String::from("nl")),
+            timestamp: Some(1_700_000_000),
+            ..Default::default()
+        };
+        let mut bytes = meta.to_bytes();
+        // Unknown records are skipped
+        bytes.extend_from_slice(&[0xff, 2, 0, 0]);
+        assert_eq!(Metadata::from(MetadataView::decode(&bytes).unwrap()), meta);
+
+        let err = MetadataView::decode(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Truncated { .. }));
+    }
+}
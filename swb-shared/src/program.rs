@@ -3,58 +3,138 @@ use core::fmt;
 #[cfg(feature = "std")]
 use std::fmt;
 
-#[cfg(not(feature = "std"))]
-use core::mem;
-#[cfg(feature = "std")]
-use std::mem;
-
-#[cfg(not(feature = "std"))]
-use core::convert::TryInto;
-#[cfg(feature = "std")]
-use std::convert::TryInto;
-
-use crate::{BinaryInstruction, Instruction, ToBinary};
+use crate::compression;
+use crate::text::blocks;
+use crate::{
+    Address, AddressRange, Anchor, BinaryInstruction, CodeEncoding, CompactEncoder, Crc32, ErrorKind,
+    Flags, Forms, Header, Image, Instruction, Link, Metadata, ProgramView, Section, SectionEntry,
+    SectionType, Text, TextCompression, ToBinary, CHECKSUM_SIZE, HEADER_SIZE,
+};
+use alloc::vec;
 use alloc::vec::Vec;
-use ascii::{AsAsciiStr, AsciiString, IntoAsciiString};
+use ascii::AsciiChar;
 
 use crate::Result;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
 pub struct Program {
-    pub text: AsciiString,
+    pub text: Text,
     pub code: Vec<Instruction>,
+    pub meta: Metadata,
+    /// Targets of the links in the code, indexed by [`Instruction::LinkStart`]
+    pub links: Vec<Link>,
+    /// Images shown by the code, indexed by [`Instruction::Image`]
+    pub images: Vec<Image>,
+    /// Forms and the controls that instructions like [`Instruction::TextInput`] refer to
+    pub forms: Forms,
+    /// Named positions in the code, indexed by [`Instruction::Anchor`]
+    pub anchors: Vec<Anchor>,
+    /// Sections this crate doesn't interpret, in file order. These must not use one of the
+    /// known section types.
+    pub sections: Vec<Section>,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct BinaryProgram {
-    pub text: AsciiString,
+    pub text: Text,
     pub code: Vec<BinaryInstruction>,
+    pub meta: Metadata,
+    pub links: Vec<Link>,
+    pub images: Vec<Image>,
+    pub forms: Forms,
+    pub anchors: Vec<Anchor>,
+    pub sections: Vec<Section>,
+    pub encoding: CodeEncoding,
+    pub compression: TextCompression,
+    pub checksum: bool,
 }
 
 impl TryFrom<&[u8]> for Program {
     type Error = crate::Error;
 
     fn try_from(value: &[u8]) -> Result<Self> {
-        let header = &value[0..8];
-        let text_len = u64::from_le_bytes(header.try_into().unwrap()) as usize;
-        let text_bytes = &value[8..8 + text_len];
-        let mut vec = Vec::with_capacity(text_len);
-        vec.extend_from_slice(text_bytes);
-        let text = vec.into_ascii_string().unwrap();
-        let instruction_bytes = &value[8 + text_len..];
-        let code = instruction_bytes
-            .chunks_exact(9)
-            .map(|bytes| {
-                let mut arr: [u8; 9] = [0, 0, 0, 0, 0, 0, 0, 0, 0];
-                arr.copy_from_slice(bytes);
-                Instruction::try_from(BinaryInstruction::try_from(arr).unwrap()).unwrap()
+        let mut scratch = vec![0; ProgramView::scratch_len(value)?];
+        let view = ProgramView::with_scratch(value, &mut scratch)?;
+        // The view already validated the data section, so this won't fail
+        let text = Text::decode(view.text().as_bytes(), view.is_utf8())?;
+        let code = view.instructions().collect::<Result<Vec<_>>>()?;
+        let meta = Metadata::from(view.metadata()?);
+        // The view already validated the link, image, form and anchor tables
+        let links = view.links().map(|link| link.map(Link::from)).collect::<Result<Vec<_>>>()?;
+        let images = view.images().map(|image| image.map(Image::from)).collect::<Result<Vec<_>>>()?;
+        let forms = Forms::decode(view.forms())?;
+        let anchors = view
+            .anchors()
+            .map(|anchor| anchor.map(Anchor::from))
+            .collect::<Result<Vec<_>>>()?;
+        // Jump targets can only be checked once the number of instructions is known
+        let jumps = links.iter().filter_map(|link| match link {
+            Link::Jump(instruction) => Some(*instruction),
+            _ => None,
+        });
+        for instruction in jumps.chain(anchors.iter().filter_map(Anchor::instruction)) {
+            if instruction as usize >= code.len() {
+                return Err(crate::Error::new(ErrorKind::JumpOutOfBounds {
+                    instruction,
+                    count: code.len(),
+                }));
+            }
+        }
+        let sections = view
+            .sections()
+            .filter(|entry| !entry.ty.is_known())
+            .map(|entry| Section {
+                ty: entry.ty,
+                data: view.section_bytes(entry).to_vec(),
             })
-            .collect::<Vec<_>>();
-        Ok(Self { text, code })
+            .collect();
+        Ok(Self {
+            text,
+            code,
+            meta,
+            links,
+            images,
+            forms,
+            anchors,
+            sections,
+        })
     }
 }
 
 impl Program {
+    /// Converts a program with UTF-8 text into one with ascii text, for readers that only
+    /// support ascii. Non-ascii characters are replaced by `replacement`, or the conversion
+    /// is refused if no replacement is given. Text ranges are adjusted to the converted text.
+    pub fn into_ascii(self, replacement: Option<AsciiChar>) -> Result<Program> {
+        if !self.text.is_utf8() {
+            return Ok(self);
+        }
+        let replacement = replacement.ok_or(crate::Error::new(ErrorKind::Utf8NotSupported))?;
+        let (text, offsets) = self.text.to_ascii_lossy(replacement);
+        let code = self
+            .code
+            .into_iter()
+            .enumerate()
+            .map(|(index, instruction)| match instruction {
+                Instruction::Text(range) => {
+                    self.text.check_range(range).map_err(|e| e.with_instruction(index))?;
+                    let start = offsets[range.base.0 as usize];
+                    let end = offsets[(range.base.0 + range.range) as usize];
+                    Ok(Instruction::Text(AddressRange {
+                        base: Address(start),
+                        range: end - start,
+                    }))
+                }
+                other => Ok(other),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Program {
+            text: Text::Ascii(text),
+            code,
+            ..self
+        })
+    }
+
     pub fn to_binary(self) -> BinaryProgram {
         BinaryProgram {
             text: self.text,
@@ -63,25 +143,123 @@ impl Program {
                 .into_iter()
                 .map(|instruction| instruction.to_binary())
                 .collect(),
+            meta: self.meta,
+            links: self.links,
+            images: self.images,
+            forms: self.forms,
+            anchors: self.anchors,
+            sections: self.sections,
+            encoding: CodeEncoding::default(),
+            compression: TextCompression::default(),
+            checksum: false,
         }
     }
 }
 
 impl BinaryProgram {
+    pub fn with_encoding(self, encoding: CodeEncoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    pub fn with_compression(self, compression: TextCompression) -> Self {
+        Self { compression, ..self }
+    }
+
+    pub fn with_checksum(self, checksum: bool) -> Self {
+        Self { checksum, ..self }
+    }
+
     pub fn into_byte_buffer(self) -> Vec<u8> {
-        let len = self.text.len() as u64;
-        let header_bytes: [u8; 8] = len.to_le_bytes();
-        let mut result = header_bytes.to_vec();
-        // We know how many more bytes we need, so this saves some allocations.
-        result.reserve(
-            (len as usize + self.code.len() * mem::size_of::<BinaryInstruction>()) as usize,
-        );
-        // Add string buffer
-        result.extend_from_slice(self.text.as_bytes());
-        // Now add our binary instructions
-        for instr in self.code {
-            let bytes = instr.into_bytes();
-            result.extend_from_slice(&bytes);
+        let compressed = match self.compression {
+            TextCompression::None => None,
+            TextCompression::Lzss => Some(compression::compress(self.text.as_bytes())),
+        };
+        let text_bytes = compressed.as_deref().unwrap_or(self.text.as_bytes());
+        // We know how many bytes we need (at most, for the compact encoding), so this saves some allocations.
+        let mut code_bytes = Vec::with_capacity(self.code.len() * BinaryInstruction::ENCODED_SIZE);
+        match self.encoding {
+            CodeEncoding::Fixed => {
+                for instr in &self.code {
+                    code_bytes.extend_from_slice(&instr.into_bytes());
+                }
+            }
+            CodeEncoding::Compact => {
+                let mut encoder = CompactEncoder::new();
+                for instr in &self.code {
+                    encoder.encode(instr, &mut code_bytes);
+                }
+            }
+        }
+
+        let meta_bytes = self.meta.to_bytes();
+        let mut sections = vec![(SectionType::TEXT, text_bytes), (SectionType::CODE, code_bytes.as_slice())];
+        if !self.meta.is_empty() {
+            sections.push((SectionType::META, meta_bytes.as_slice()));
+        }
+        let mut link_bytes = Vec::new();
+        for link in &self.links {
+            link.encode(&mut link_bytes);
+        }
+        if !self.links.is_empty() {
+            sections.push((SectionType::LINKS, link_bytes.as_slice()));
+        }
+        let mut image_bytes = Vec::new();
+        for image in &self.images {
+            image.encode(&mut image_bytes);
+        }
+        if !self.images.is_empty() {
+            sections.push((SectionType::IMAGES, image_bytes.as_slice()));
+        }
+        let form_bytes = self.forms.to_bytes();
+        if !self.forms.is_empty() {
+            sections.push((SectionType::FORMS, form_bytes.as_slice()));
+        }
+        let mut anchor_bytes = Vec::new();
+        for anchor in &self.anchors {
+            anchor.encode(&mut anchor_bytes);
+        }
+        if !self.anchors.is_empty() {
+            sections.push((SectionType::ANCHORS, anchor_bytes.as_slice()));
+        }
+        for section in &self.sections {
+            debug_assert!(!section.ty.is_known(), "known sections can't be added as raw sections");
+            sections.push((section.ty, section.data.as_slice()));
+        }
+
+        let flags = Flags::default()
+            .with(Flags::UTF8_TEXT, self.text.is_utf8())
+            .with(Flags::COMPACT_CODE, self.encoding == CodeEncoding::Compact)
+            .with(Flags::COMPRESSED_TEXT, compressed.is_some())
+            .with(Flags::CHECKSUM, self.checksum);
+        let header = Header::new(flags, sections.len() as u32);
+        let table_end = header.table_end() as usize;
+        let data_len: usize = sections.iter().map(|(_, data)| data.len()).sum();
+        let mut result = Vec::with_capacity(table_end + data_len);
+        result.extend_from_slice(&header.into_bytes());
+        if self.checksum {
+            // Filled in once the rest of the file is known
+            result.extend_from_slice(&[0; CHECKSUM_SIZE]);
+        }
+        // The sections are stored in table order, right after the table
+        let mut offset = table_end;
+        for (ty, data) in &sections {
+            let entry = SectionEntry {
+                ty: *ty,
+                offset: offset as u32,
+                len: data.len() as u32,
+            };
+            result.extend_from_slice(&entry.into_bytes());
+            offset += data.len();
+        }
+        for (_, data) in &sections {
+            result.extend_from_slice(data);
+        }
+        if self.checksum {
+            let mut crc = Crc32::new();
+            crc.update(&result[..HEADER_SIZE]);
+            crc.update(&result[HEADER_SIZE + CHECKSUM_SIZE..]);
+            result[HEADER_SIZE..HEADER_SIZE + CHECKSUM_SIZE]
+                .copy_from_slice(&crc.finish().to_le_bytes());
         }
         result
     }
@@ -99,22 +277,34 @@ impl fmt::Display for BinaryProgram {
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         const BLOCK_SIZE: usize = 16;
-        write!(f, ".data\n")?;
-        // Display our text buffer, we do this in blocks of at most 16 characters
-        let mut cur = 0;
-        while cur < self.text.len() {
-            // Grab at most 16 characters, but never more than the amount of remaining characters
-            let remaining = (self.text.len() - cur).min(BLOCK_SIZE);
-            if remaining == 0 {
-                break;
+        if !self.meta.is_empty() {
+            writeln!(f, ".meta")?;
+            let texts = [
+                ("title", &self.meta.title),
+                ("url", &self.meta.url),
+                ("lang", &self.meta.lang),
+            ];
+            for (name, value) in texts {
+                if let Some(value) = value {
+                    writeln!(f, "\t{name}\t{value:?}")?;
+                }
+            }
+            if let Some(timestamp) = self.meta.timestamp {
+                writeln!(f, "\ttimestamp\t{timestamp}")?;
+            }
+            if let Some(compiler) = &self.meta.compiler {
+                writeln!(f, "\tcompiler\t{compiler:?}")?;
             }
-            let block = self.text.as_slice().get(cur..(cur + remaining)).unwrap();
-            let block = unsafe {
-                // SAFETY: This slice was created from an AsciiString, so we know there are no non-ascii characters.
-                block.as_ascii_str_unchecked()
-            };
-            write!(f, "\t{:#06x}\t{}\n", cur, block)?;
-            cur += remaining;
+        }
+        if self.text.is_utf8() {
+            writeln!(f, ".data utf8")?;
+        } else {
+            write!(f, ".data\n")?;
+        }
+        // Display our text buffer, we do this in blocks of at most 16 bytes without splitting characters.
+        // Blocks are quoted so whitespace and newlines survive being read back by `crate::asm`.
+        for (cur, block) in blocks(self.text.as_str(), BLOCK_SIZE) {
+            writeln!(f, "\t{:#06x}\t{:?}", cur, block)?;
         }
 
         write!(f, ".text\n")?;
@@ -122,6 +312,108 @@ impl fmt::Display for Program {
             write!(f, "\t{instruction}\n")?;
         }
 
+        if !self.links.is_empty() {
+            writeln!(f, ".links")?;
+            for (index, link) in self.links.iter().enumerate() {
+                match link {
+                    Link::Url(url) => writeln!(f, "\t{index}\t{url:?}")?,
+                    Link::Jump(instruction) => writeln!(f, "\t{index}\tjump {instruction}")?,
+                    Link::Unknown { kind, data } => {
+                        write!(f, "\t{index}\t{kind:#04x}")?;
+                        for byte in data {
+                            write!(f, " {byte:02x}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+            }
+        }
+
+        if !self.anchors.is_empty() {
+            writeln!(f, ".anchors")?;
+            for (index, anchor) in self.anchors.iter().enumerate() {
+                match anchor {
+                    Anchor::Named { name, instruction } => {
+                        writeln!(f, "\t{index}\t{name:?}\t{instruction}")?
+                    }
+                    Anchor::Unknown { kind, data } => {
+                        write!(f, "\t{index}\t{kind:#04x}")?;
+                        for byte in data {
+                            write!(f, " {byte:02x}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+            }
+        }
+
+        if !self.images.is_empty() {
+            writeln!(f, ".images")?;
+            for (index, image) in self.images.iter().enumerate() {
+                match image {
+                    // One line per row, with `#` for black and `.` for white pixels
+                    Image::Mono(bitmap) => {
+                        writeln!(f, "\t{index}\tmono {}x{}", bitmap.width, bitmap.height)?;
+                        let view = bitmap.view();
+                        for y in 0..bitmap.height {
+                            write!(f, "\t\t")?;
+                            for x in 0..bitmap.width {
+                                write!(f, "{}", if view.pixel(x, y) { '#' } else { '.' })?;
+                            }
+                            writeln!(f)?;
+                        }
+                    }
+                    Image::Unknown { format, data } => {
+                        write!(f, "\t{index}\t{format:#04x}")?;
+                        for byte in data {
+                            write!(f, " {byte:02x}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+            }
+        }
+
+        if !self.forms.is_empty() {
+            writeln!(f, ".forms")?;
+            for (index, form) in self.forms.forms.iter().enumerate() {
+                writeln!(f, "\tform {index}\t{}\t{:?}", form.method.as_str(), form.action)?;
+            }
+            for (index, control) in self.forms.controls.iter().enumerate() {
+                write!(
+                    f,
+                    "\t{} {index}\tform {}\t{:?}\t{:?}",
+                    control.kind.as_str(),
+                    control.form,
+                    control.name,
+                    control.value
+                )?;
+                if control.checked {
+                    write!(f, "\tchecked")?;
+                }
+                writeln!(f)?;
+                for option in &control.options {
+                    write!(f, "\t\toption\t{:?}\t{:?}", option.value, option.label)?;
+                    if option.selected {
+                        write!(f, "\tselected")?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+
+        for section in &self.sections {
+            writeln!(f, ".section {:#x}", section.ty.0)?;
+            for (i, chunk) in section.data.chunks(BLOCK_SIZE).enumerate() {
+                write!(f, "\t{:#06x}\t", i * BLOCK_SIZE)?;
+                for (j, byte) in chunk.iter().enumerate() {
+                    let sep = if j == 0 { "" } else { " " };
+                    write!(f, "{sep}{byte:02x}")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -130,12 +422,14 @@ impl fmt::Display for Program {
 #[cfg(test)]
 mod tests {
     use crate::*;
-    use ascii::AsciiString;
+    #[cfg(not(feature = "std"))]
+    use alloc::{string::{String, ToString}, vec};
+    use ascii::{AsciiChar, AsciiString};
 
     #[test]
     fn test_from_binary() {
         let program = Program {
-            text: AsciiString::from_ascii(*b"Hello").unwrap(),
+            text: Text::Ascii(AsciiString::from_ascii(*b"Hello").unwrap()),
             code: vec![
                 Instruction::Push(StyleVar::Bold),
                 Instruction::Text(AddressRange {
@@ -145,10 +439,502 @@ mod tests {
                 Instruction::Pop(StyleVar::Bold),
                 Instruction::Stop,
             ],
+            ..Default::default()
         };
         let bytes = program.clone().to_binary().into_byte_buffer();
         let converted = Program::try_from(bytes.as_slice());
         assert!(converted.is_ok());
         assert_eq!(program, converted.unwrap());
     }
+
+    #[test]
+    fn test_compact_encoding() {
+        // Unknown styles, alignments and rule styles can only come from decoding
+        let unknown_style = crate::instruction::parse_style_var(0x80).unwrap();
+        let unknown_align = crate::instruction::parse_align_value(7);
+        let unknown_rule_style = crate::instruction::parse_rule_style(9);
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"Hello world").unwrap()),
+            code: vec![
+                Instruction::PushHeading(2),
+                Instruction::Text(AddressRange {
+                    base: Address(6),
+                    range: 5,
+                }),
+                Instruction::PopHeading(2),
+                Instruction::ListStart(ListKind::Ordered(3)),
+                Instruction::ListItem,
+                Instruction::ListStart(ListKind::Unordered),
+                Instruction::ListItem,
+                Instruction::ListEnd,
+                Instruction::ListEnd,
+                Instruction::TableStart,
+                Instruction::TableRow,
+                Instruction::TableHeaderCell,
+                Instruction::TableCell,
+                Instruction::Image(0),
+                Instruction::TableEnd,
+                Instruction::ParagraphStart,
+                Instruction::Indent(2),
+                Instruction::BlockBreak,
+                Instruction::Indent(0),
+                Instruction::ParagraphEnd,
+                Instruction::PreformattedStart,
+                Instruction::PreformattedEnd,
+                Instruction::PushAlign(Align::Center),
+                Instruction::PushAlign(unknown_align),
+                Instruction::PopAlign(unknown_align),
+                Instruction::PopAlign(Align::Center),
+                Instruction::Rule(Rule::default()),
+                Instruction::Rule(Rule {
+                    thickness: 3,
+                    style: unknown_rule_style,
+                }),
+                Instruction::Endl,
+                Instruction::Push(StyleVar::Italic),
+                Instruction::Push(StyleVar::Monospace),
+                Instruction::Push(unknown_style),
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 5,
+                }),
+                Instruction::Pop(unknown_style),
+                Instruction::Pop(StyleVar::Monospace),
+                Instruction::Pop(StyleVar::Italic),
+                Instruction::Stop,
+            ],
+            images: vec![Image::Mono(Bitmap::new(1, 1))],
+            ..Default::default()
+        };
+        let fixed = program.clone().to_binary().into_byte_buffer();
+        let compact = program
+            .clone()
+            .to_binary()
+            .with_encoding(CodeEncoding::Compact)
+            .into_byte_buffer();
+        assert!(compact.len() < fixed.len());
+        assert_eq!(program, Program::try_from(compact.as_slice()).unwrap());
+
+        // A varint running off the end of the code section is a truncation, not a panic
+        let mut short_code = compact.clone();
+        let code_len = HEADER_SIZE + SECTION_ENTRY_SIZE + 8;
+        let len = u32::from_le_bytes(short_code[code_len..code_len + 4].try_into().unwrap());
+        short_code[code_len..code_len + 4].copy_from_slice(&(len - 4).to_le_bytes());
+        let err = Program::try_from(short_code.as_slice()).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Truncated { .. }));
+
+        let compressed = program
+            .clone()
+            .to_binary()
+            .with_compression(TextCompression::Lzss)
+            .into_byte_buffer();
+        assert_eq!(program, Program::try_from(compressed.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_checksum() {
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"Hello").unwrap()),
+            code: vec![
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 5,
+                }),
+                Instruction::Stop,
+            ],
+            ..Default::default()
+        };
+        let bytes = program.clone().to_binary().with_checksum(true).into_byte_buffer();
+        assert_eq!(program, Program::try_from(bytes.as_slice()).unwrap());
+
+        // A bit flip in a text range operand must be detected
+        let mut corrupt = bytes;
+        let len = corrupt.len();
+        corrupt[len - 13] ^= 1;
+        let err = Program::try_from(corrupt.as_slice()).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::ChecksumMismatch { .. }));
+    }
+
+    #[test]
+    fn test_rejects_bad_header() {
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"Hello").unwrap()),
+            code: vec![Instruction::Stop],
+            ..Default::default()
+        };
+        let bytes = program.to_binary().into_byte_buffer();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(Program::try_from(bad_magic.as_slice()).is_err());
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let err = Program::try_from(newer.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnsupportedVersion { version: FORMAT_VERSION + 1 });
+
+        let mut old = bytes.clone();
+        old[4..6].copy_from_slice(&1u16.to_le_bytes());
+        let err = Program::try_from(old.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnsupportedVersion { version: 1 });
+
+        let mut unknown_flags = bytes;
+        unknown_flags[6] = 0x80;
+        assert!(Program::try_from(unknown_flags.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"Hello").unwrap()),
+            code: vec![Instruction::Stop],
+            meta: Metadata {
+                title: Some(String::from("A \"quoted\" title")),
+                timestamp: Some(1_700_000_000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let bytes = program.clone().to_binary().into_byte_buffer();
+        let view = ProgramView::new(&bytes).unwrap();
+        assert_eq!(view.metadata().unwrap().title, Some("A \"quoted\" title"));
+        assert_eq!(program, Program::try_from(bytes.as_slice()).unwrap());
+        assert!(program
+            .to_string()
+            .starts_with(".meta\n\ttitle\t\"A \\\"quoted\\\" title\"\n\ttimestamp\t1700000000\n.data\n"));
+    }
+
+    #[test]
+    fn test_links() {
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"Click here").unwrap()),
+            code: vec![
+                Instruction::LinkStart(1),
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 10,
+                }),
+                Instruction::LinkEnd,
+                Instruction::Stop,
+            ],
+            links: vec![
+                Link::Unknown {
+                    kind: 0x7f,
+                    data: vec![1, 2],
+                },
+                Link::Url(String::from("https://example.com/")),
+            ],
+            ..Default::default()
+        };
+        for encoding in [CodeEncoding::Fixed, CodeEncoding::Compact] {
+            let bytes = program.clone().to_binary().with_encoding(encoding).into_byte_buffer();
+            let view = ProgramView::new(&bytes).unwrap();
+            assert_eq!(view.links().get(1), Some(Ok(LinkTarget::Url("https://example.com/"))));
+            assert_eq!(program, Program::try_from(bytes.as_slice()).unwrap());
+        }
+        assert!(program
+            .to_string()
+            .contains("\tlink 1\n\ttext 0x0000..0x0009\n\tendlink\n\tstop\n.links\n\t0\t0x7f 01 02\n\t1\t\"https://example.com/\"\n"));
+
+        let mut missing = program;
+        missing.links.pop();
+        let bytes = missing.to_binary().into_byte_buffer();
+        let err = Program::try_from(bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::LinkOutOfBounds { index: 1, count: 1 });
+        assert_eq!(err.instruction, Some(0));
+    }
+
+    #[test]
+    fn test_images() {
+        let mut bitmap = Bitmap::new(3, 2);
+        bitmap.set_pixel(0, 0, true);
+        bitmap.set_pixel(2, 1, true);
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"").unwrap()),
+            code: vec![Instruction::Image(0), Instruction::Stop],
+            images: vec![Image::Mono(bitmap)],
+            ..Default::default()
+        };
+        for encoding in [CodeEncoding::Fixed, CodeEncoding::Compact] {
+            let bytes = program.clone().to_binary().with_encoding(encoding).into_byte_buffer();
+            let view = ProgramView::new(&bytes).unwrap();
+            assert!(matches!(view.images().get(0), Some(Ok(ImageView::Mono(_)))));
+            assert_eq!(program, Program::try_from(bytes.as_slice()).unwrap());
+        }
+        assert!(program.to_string().ends_with("\timage 0\n\tstop\n.images\n\t0\tmono 3x2\n\t\t#..\n\t\t..#\n"));
+
+        let mut missing = program;
+        missing.images.clear();
+        let bytes = missing.to_binary().into_byte_buffer();
+        let err = Program::try_from(bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ImageOutOfBounds { index: 0, count: 0 });
+    }
+
+    #[test]
+    fn test_forms() {
+        let control = |kind, name: &str, value: &str| Control {
+            kind,
+            form: 0,
+            name: String::from(name),
+            value: String::from(value),
+            checked: false,
+            options: vec![],
+        };
+        let mut select = control(ControlKind::Select, "lang", "");
+        select.options = vec![SelectOption {
+            value: String::from("nl"),
+            label: String::from("Dutch"),
+            selected: true,
+        }];
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"").unwrap()),
+            code: vec![
+                Instruction::TextInput(0),
+                Instruction::Select(1),
+                Instruction::Submit(3),
+                Instruction::Stop,
+            ],
+            forms: Forms {
+                forms: vec![Form {
+                    method: FormMethod::Get,
+                    action: String::from("/search"),
+                }],
+                controls: vec![
+                    control(ControlKind::TextInput, "q", ""),
+                    select,
+                    control(ControlKind::Hidden, "token", "abc"),
+                    control(ControlKind::Submit, "", "Search"),
+                ],
+            },
+            ..Default::default()
+        };
+        for encoding in [CodeEncoding::Fixed, CodeEncoding::Compact] {
+            let bytes = program.clone().to_binary().with_encoding(encoding).into_byte_buffer();
+            let view = ProgramView::new(&bytes).unwrap();
+            let select = view.forms().control(1).unwrap().unwrap();
+            assert_eq!(select.options().next().unwrap().label, "Dutch");
+            assert_eq!(program, Program::try_from(bytes.as_slice()).unwrap());
+        }
+        assert!(program.to_string().contains(
+            ".forms\n\tform 0\tget\t\"/search\"\n\tinput 0\tform 0\t\"q\"\t\"\"\n\tselect 1\tform 0\t\"lang\"\t\"\"\n\t\toption\t\"nl\"\t\"Dutch\"\tselected\n"
+        ));
+
+        // Controls shown out of order are found too
+        let mut reordered = program.clone();
+        reordered.code = vec![
+            Instruction::Submit(3),
+            Instruction::Select(1),
+            Instruction::TextInput(0),
+            Instruction::Submit(3),
+            Instruction::Stop,
+        ];
+        let bytes = reordered.clone().to_binary().into_byte_buffer();
+        assert_eq!(reordered, Program::try_from(bytes.as_slice()).unwrap());
+
+        let mut mismatch = program.clone();
+        mismatch.code[2] = Instruction::Submit(2);
+        let bytes = mismatch.to_binary().into_byte_buffer();
+        let err = Program::try_from(bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ControlKindMismatch { index: 2 });
+
+        let mut orphan = program;
+        orphan.forms.controls[3].form = 1;
+        let bytes = orphan.to_binary().into_byte_buffer();
+        let err = Program::try_from(bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::FormOutOfBounds { control: 3, count: 1 });
+    }
+
+    #[test]
+    fn test_anchors() {
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"Intro").unwrap()),
+            code: vec![
+                Instruction::LinkStart(0),
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 5,
+                }),
+                Instruction::LinkEnd,
+                Instruction::Anchor(0),
+                Instruction::Stop,
+            ],
+            links: vec![Link::Jump(3)],
+            anchors: vec![Anchor::Named {
+                name: String::from("intro"),
+                instruction: 3,
+            }],
+            ..Default::default()
+        };
+        for encoding in [CodeEncoding::Fixed, CodeEncoding::Compact] {
+            let bytes = program.clone().to_binary().with_encoding(encoding).into_byte_buffer();
+            let view = ProgramView::new(&bytes).unwrap();
+            assert_eq!(view.links().get(0), Some(Ok(LinkTarget::Jump(3))));
+            assert_eq!(view.anchors().find("intro").unwrap().unwrap().instruction(), Some(3));
+            assert_eq!(program, Program::try_from(bytes.as_slice()).unwrap());
+        }
+        assert!(program
+            .to_string()
+            .contains("\tanchor 0\n\tstop\n.links\n\t0\tjump 3\n.anchors\n\t0\t\"intro\"\t3\n"));
+
+        // Anchors of kinds added later are kept, but can't be found by name
+        let mut unknown = program.clone();
+        unknown.anchors.insert(
+            0,
+            Anchor::Unknown {
+                kind: 9,
+                data: vec![1, 2],
+            },
+        );
+        unknown.code[3] = Instruction::Anchor(1);
+        let bytes = unknown.clone().to_binary().into_byte_buffer();
+        let view = ProgramView::new(&bytes).unwrap();
+        assert_eq!(view.anchors().find("intro").unwrap().unwrap().instruction(), Some(3));
+        assert_eq!(view.anchors().get(0).unwrap().unwrap().name(), None);
+        assert_eq!(unknown, Program::try_from(bytes.as_slice()).unwrap());
+        assert!(unknown.to_string().contains(".anchors\n\t0\t0x09 01 02\n\t1\t\"intro\"\t3\n"));
+
+        let mut past_end = program.clone();
+        past_end.links[0] = Link::Jump(5);
+        let bytes = past_end.to_binary().into_byte_buffer();
+        let err = Program::try_from(bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::JumpOutOfBounds { instruction: 5, count: 5 });
+
+        let mut missing = program;
+        missing.anchors.clear();
+        let bytes = missing.to_binary().into_byte_buffer();
+        let err = Program::try_from(bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::AnchorOutOfBounds { index: 0, count: 0 });
+        assert_eq!(err.instruction, Some(3));
+    }
+
+    #[test]
+    fn test_unknown_sections() {
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"Hello").unwrap()),
+            code: vec![Instruction::Stop],
+            sections: vec![Section {
+                ty: SectionType(0xf00),
+                data: vec![1, 2, 3],
+            }],
+            ..Default::default()
+        };
+        let bytes = program.clone().to_binary().with_checksum(true).into_byte_buffer();
+        let view = ProgramView::new(&bytes).unwrap();
+        assert_eq!(view.sections().count(), 3);
+        assert_eq!(view.section(SectionType(0xf00)), Some([1, 2, 3].as_slice()));
+        assert_eq!(view.section(SectionType(0xf01)), None);
+        // Unknown sections are passed on as they are
+        assert_eq!(program, Program::try_from(bytes.as_slice()).unwrap());
+        assert!(program.to_string().ends_with(".section 0xf00\n\t0x0000\t01 02 03\n"));
+
+        // A file without a code section can't be read
+        let mut missing = program.to_binary().into_byte_buffer();
+        let code_entry = HEADER_SIZE + SECTION_ENTRY_SIZE;
+        missing[code_entry..code_entry + 4].copy_from_slice(&0xf01u32.to_le_bytes());
+        let err = Program::try_from(missing.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::MissingSection { ty: SectionType::CODE.0 });
+    }
+
+    #[test]
+    fn test_rejects_corrupt_buffers() {
+        let program = Program {
+            text: Text::Ascii(AsciiString::from_ascii(*b"Hello").unwrap()),
+            code: vec![
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 5,
+                }),
+                Instruction::Stop,
+            ],
+            ..Default::default()
+        };
+        let bytes = program.to_binary().into_byte_buffer();
+        let code_start = HEADER_SIZE + 2 * SECTION_ENTRY_SIZE + 5;
+
+        // Every truncation must produce an error instead of a panic
+        for len in 0..bytes.len() {
+            assert!(Program::try_from(&bytes[..len]).is_err());
+        }
+
+        let err = Program::try_from(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::SectionOutOfBounds { .. }));
+
+        // A code section that ends in the middle of an instruction
+        let mut short_code = bytes.clone();
+        let code_len = HEADER_SIZE + SECTION_ENTRY_SIZE + 8;
+        short_code[code_len..code_len + 4].copy_from_slice(&17u32.to_le_bytes());
+        let err = Program::try_from(short_code.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Truncated { needed: 9, available: 8 });
+        assert_eq!(err.offset, Some(code_start + 9));
+        assert_eq!(err.instruction, Some(1));
+
+        let mut bad_opcode = bytes.clone();
+        bad_opcode[code_start + 9] = 0xff;
+        let err = Program::try_from(bad_opcode.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOpcode { opcode: 0xff });
+        assert_eq!(err.offset, Some(code_start + 9));
+        assert_eq!(err.instruction, Some(1));
+
+        // A code section that starts inside the data section, which all decoders reject
+        let mut overlapping = bytes.clone();
+        let code_offset = HEADER_SIZE + SECTION_ENTRY_SIZE + 4;
+        overlapping[code_offset..code_offset + 4].copy_from_slice(&(code_start as u32 - 2).to_le_bytes());
+        let err = Program::try_from(overlapping.as_slice()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OverlappingSections { ty: SectionType::CODE.0 });
+        assert_eq!(err.offset, Some(HEADER_SIZE + SECTION_ENTRY_SIZE));
+        let mut decoder = StreamDecoder::new();
+        let stream_err = decoder.feed(&overlapping).find_map(|event| event.err()).unwrap();
+        assert_eq!(stream_err, err);
+
+        let heading = BinaryInstruction { ty: 7, arg: 7 };
+        let err = Instruction::try_from(heading).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidHeadingLevel { level: 7 });
+
+        let list = BinaryInstruction { ty: 9, arg: 0x100 };
+        let err = Instruction::try_from(list).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperand { opcode: 9 });
+
+        let mut out_of_bounds = bytes;
+        out_of_bounds[code_start + 5] = 6;
+        let err = Program::try_from(out_of_bounds.as_slice()).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::TextOutOfBounds { .. }));
+        assert_eq!(err.offset, Some(code_start));
+    }
+
+    #[test]
+    fn test_utf8_text() {
+        let program = Program {
+            text: Text::from(String::from("Zoë pays €5")),
+            code: vec![
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 4,
+                }),
+                Instruction::Text(AddressRange {
+                    base: Address(10),
+                    range: 4,
+                }),
+                Instruction::Stop,
+            ],
+            ..Default::default()
+        };
+        let bytes = program.clone().to_binary().into_byte_buffer();
+        let converted = Program::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(program, converted);
+        assert_eq!(converted.text.get(AddressRange { base: Address(10), range: 4 }), Some("€5"));
+
+        assert_eq!(
+            program.clone().into_ascii(None).unwrap_err().kind,
+            ErrorKind::Utf8NotSupported
+        );
+        let ascii = program.into_ascii(Some(AsciiChar::Question)).unwrap();
+        assert_eq!(ascii.text.as_str(), "Zo? pays ?5");
+        assert_eq!(
+            ascii.code[1],
+            Instruction::Text(AddressRange {
+                base: Address(9),
+                range: 2,
+            })
+        );
+    }
 }
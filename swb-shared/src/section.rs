@@ -0,0 +1,199 @@
+#[cfg(not(feature = "std"))]
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::convert::TryInto;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::write_varint;
+use crate::{read_varint, ErrorKind, Result};
+
+/// Size of one entry in the section table in bytes.
+pub const SECTION_ENTRY_SIZE: usize = 12;
+
+/// Identifies what a section contains. Readers skip sections with a type they don't know,
+/// so new kinds of sections can be added without breaking old readers.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SectionType(pub u32);
+
+impl SectionType {
+    /// The data section, holding the text that `Text` instructions refer to.
+    pub const TEXT: SectionType = SectionType(1);
+
+    /// The code section, holding the instructions.
+    pub const CODE: SectionType = SectionType(2);
+
+    /// Information about the page, see [`crate::MetadataView`].
+    pub const META: SectionType = SectionType(3);
+
+    /// Targets of links, see [`crate::LinkTable`].
+    pub const LINKS: SectionType = SectionType(4);
+
+    /// Images, see [`crate::ImageTable`].
+    pub const IMAGES: SectionType = SectionType(5);
+
+    /// Forms and their controls, see [`crate::FormTable`].
+    pub const FORMS: SectionType = SectionType(6);
+
+    /// Named positions in the code, see [`crate::AnchorTable`].
+    pub const ANCHORS: SectionType = SectionType(7);
+
+    /// Number of section types understood by this version of the crate.
+    pub(crate) const KNOWN_COUNT: usize = 7;
+
+    /// Index of a known section type, used to keep track of them in fixed size arrays.
+    pub(crate) fn index(self) -> Option<usize> {
+        match self {
+            Self::TEXT => Some(0),
+            Self::CODE => Some(1),
+            Self::META => Some(2),
+            Self::LINKS => Some(3),
+            Self::IMAGES => Some(4),
+            Self::FORMS => Some(5),
+            Self::ANCHORS => Some(6),
+            _ => None,
+        }
+    }
+
+    pub fn is_known(self) -> bool {
+        self.index().is_some()
+    }
+}
+
+/// An entry in the section table.
+///
+/// Layout:
+/// - 4 bytes section type (little endian)
+/// - 4 bytes offset of the section from the start of the file (little endian)
+/// - 4 bytes length of the section (little endian)
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SectionEntry {
+    pub ty: SectionType,
+    pub offset: u32,
+    pub len: u32,
+}
+
+impl SectionEntry {
+    pub fn into_bytes(self) -> [u8; SECTION_ENTRY_SIZE] {
+        let mut result = [0; SECTION_ENTRY_SIZE];
+        result[0..4].copy_from_slice(&self.ty.0.to_le_bytes());
+        result[4..8].copy_from_slice(&self.offset.to_le_bytes());
+        result[8..12].copy_from_slice(&self.len.to_le_bytes());
+        result
+    }
+
+    pub fn from_bytes(bytes: [u8; SECTION_ENTRY_SIZE]) -> Self {
+        Self {
+            ty: SectionType(u32::from_le_bytes(bytes[0..4].try_into().unwrap())),
+            offset: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            len: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+        }
+    }
+
+    /// Offset of the first byte after this section.
+    pub fn end(&self) -> u64 {
+        self.offset as u64 + self.len as u64
+    }
+
+    /// Checks that this section starts after the section table and ends before `file_len`.
+    pub(crate) fn check_bounds(&self, table_end: u64, file_len: u64) -> Result<()> {
+        if (self.offset as u64) < table_end || self.end() > file_len {
+            return Err(crate::Error::new(ErrorKind::SectionOutOfBounds {
+                ty: self.ty.0,
+                offset: self.offset,
+                len: self.len,
+            }));
+        }
+        Ok(())
+    }
+}
+
+/// A section that isn't interpreted by this crate, kept as raw bytes so tools can pass it on.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Section {
+    pub ty: SectionType,
+    pub data: Vec<u8>,
+}
+
+/// Reads a record from the start of `bytes`, as used by sections that hold a list of values:
+/// a key byte, the LEB128 encoded length of the value and the value itself. Returns the key,
+/// the value and the size of the record. Offsets in errors are relative to the start of `bytes`.
+pub(crate) fn read_record(bytes: &[u8]) -> Result<(u8, &[u8], usize)> {
+    let key = *bytes.first().ok_or(crate::Error::at(
+        0,
+        ErrorKind::Truncated {
+            needed: 1,
+            available: 0,
+        },
+    ))?;
+    let (len, len_size) = read_varint(&bytes[1..]).map_err(|e| e.with_offset(1))?;
+    let start = 1 + len_size;
+    let value = usize::try_from(len)
+        .ok()
+        .and_then(|len| bytes.get(start..start.checked_add(len)?))
+        .ok_or(crate::Error::at(
+            start,
+            ErrorKind::Truncated {
+                needed: len as usize,
+                available: bytes.len() - start,
+            },
+        ))?;
+    Ok((key, value, start + value.len()))
+}
+
+/// Appends a record in the layout read by [`read_record`].
+#[cfg(feature = "alloc")]
+pub(crate) fn write_record(key: u8, value: &[u8], out: &mut Vec<u8>) {
+    out.push(key);
+    write_varint(value.len() as u64, out);
+    out.extend_from_slice(value);
+}
+
+/// Iterates over the entries of an encoded section table.
+#[derive(Debug, Clone)]
+pub struct SectionTable<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SectionTable<'a> {
+    /// `bytes` must hold a whole number of entries.
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Records an entry of a known section type in `known`, by [`SectionType::index`], and
+    /// ignores other entries. Every decoder reads the table through this, so they all reject
+    /// the same tables. Sections of known types may not overlap, so a reader can interpret them
+    /// separately, even while streaming.
+    pub(crate) fn add_known(
+        known: &mut [Option<SectionEntry>; SectionType::KNOWN_COUNT],
+        entry: SectionEntry,
+    ) -> Result<()> {
+        let Some(index) = entry.ty.index() else {
+            return Ok(());
+        };
+        if known[index].is_some() {
+            return Err(crate::Error::new(ErrorKind::DuplicateSection { ty: entry.ty.0 }));
+        }
+        for other in known.iter().flatten() {
+            if (entry.offset as u64) < other.end() && (other.offset as u64) < entry.end() {
+                return Err(crate::Error::new(ErrorKind::OverlappingSections { ty: entry.ty.0 }));
+            }
+        }
+        known[index] = Some(entry);
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for SectionTable<'a> {
+    type Item = SectionEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.bytes.get(..SECTION_ENTRY_SIZE)?;
+        self.bytes = &self.bytes[SECTION_ENTRY_SIZE..];
+        Some(SectionEntry::from_bytes(entry.try_into().unwrap()))
+    }
+}
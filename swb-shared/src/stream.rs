@@ -0,0 +1,625 @@
+use crate::{
+    BinaryInstruction, CodeEncoding, CompactDecoder, Crc32, ErrorKind, Flags, Header, Instruction,
+    Result, SectionEntry, SectionTable, SectionType, CHECKSUM_SIZE, HEADER_SIZE, MAGIC, MAX_VARINT_SIZE,
+    SECTION_ENTRY_SIZE,
+};
+
+/// Longest possible encoded instruction: a compact text instruction with two maximum size varints.
+const MAX_INSTRUCTION_SIZE: usize = 1 + 2 * MAX_VARINT_SIZE;
+
+/// Something decoded by a [`StreamDecoder`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Event<'a> {
+    Header(Header),
+    /// A piece of the data section, starting at `offset` in the data section. Fragments are
+    /// validated, but UTF-8 characters may be split across fragments.
+    Text { offset: usize, bytes: &'a [u8] },
+    Instruction(Instruction),
+    /// A piece of another known section, like [`SectionType::META`], starting at `offset` in
+    /// that section. These are passed on as they are, so they can be collected and decoded
+    /// once complete.
+    Section { ty: SectionType, offset: usize, bytes: &'a [u8] },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Header,
+    Checksum,
+    Table,
+    /// Everything after the section table, where the sections are.
+    Body,
+    Failed(crate::Error),
+}
+
+/// Push-style decoder for programs that arrive in chunks and don't fit in memory as a whole.
+/// Feed it chunks with [`StreamDecoder::feed`] and handle the events as they come out; once
+/// all input is fed, [`StreamDecoder::finish`] reports whether the program was complete.
+///
+/// Only the header, the locations of known sections and one partial instruction are buffered.
+/// Sections are decoded in file order, so the data and code sections may come in either order,
+/// but they must not overlap. Bytes outside of them, like unknown sections, are skipped.
+/// Compressed data sections can't be decoded this way, and are rejected with
+/// [`ErrorKind::UnsupportedFlags`]. Link, image, control and anchor indices aren't checked, since
+/// their tables may come after the code. Neither is whether text ranges split a UTF-8
+/// character, since that needs the whole data section, not just its length.
+#[derive(Debug, Clone)]
+pub struct StreamDecoder {
+    state: State,
+    header: Option<Header>,
+    buf: [u8; MAX_INSTRUCTION_SIZE],
+    buf_len: usize,
+    /// Section table entries that still have to be read
+    table_left: u32,
+    /// Known sections, by [`SectionType::index`]
+    sections: [Option<SectionEntry>; SectionType::KNOWN_COUNT],
+    /// Start of a UTF-8 character that is split across chunks of the data section
+    utf8_pending: [u8; 4],
+    utf8_pending_len: usize,
+    compact: CompactDecoder,
+    /// Checksum stored in the file, if it has one
+    checksum: Option<u32>,
+    /// Checksum of everything consumed so far, except the stored checksum
+    crc: Crc32,
+    /// Total number of bytes consumed
+    offset: usize,
+    index: usize,
+}
+
+impl Default for StreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: State::Header,
+            header: None,
+            buf: [0; MAX_INSTRUCTION_SIZE],
+            buf_len: 0,
+            table_left: 0,
+            sections: [None; SectionType::KNOWN_COUNT],
+            utf8_pending: [0; 4],
+            utf8_pending_len: 0,
+            compact: CompactDecoder::new(),
+            checksum: None,
+            crc: Crc32::new(),
+            offset: 0,
+            index: 0,
+        }
+    }
+
+    /// The header, once it has been decoded
+    pub fn header(&self) -> Option<Header> {
+        self.header
+    }
+
+    /// Decodes as much of `chunk` as possible. Incomplete data at the end of the chunk is kept
+    /// until the next call. The returned iterator must be drained to consume the whole chunk,
+    /// and stops after the first error.
+    pub fn feed<'d, 'a>(&'d mut self, chunk: &'a [u8]) -> Events<'d, 'a> {
+        Events {
+            decoder: self,
+            chunk,
+            pos: 0,
+        }
+    }
+
+    /// Checks that the input ended on a complete program, and that its checksum matches if it
+    /// has one. Events are emitted before the checksum can be verified, so anything decoded
+    /// from a file that fails here should be thrown away. Sections after the last known
+    /// section can't be checked for completeness without a checksum.
+    pub fn finish(&self) -> Result<()> {
+        let truncated = |needed: usize, available: usize| {
+            crate::Error::at(self.offset, ErrorKind::Truncated { needed, available })
+        };
+        match self.state {
+            State::Header => Err(truncated(HEADER_SIZE, self.buf_len)),
+            State::Checksum => Err(truncated(CHECKSUM_SIZE, self.buf_len)),
+            State::Table => Err(truncated(SECTION_ENTRY_SIZE, self.buf_len)),
+            State::Body if self.buf_len > 0 => {
+                Err(truncated(self.buf_len + 1, self.buf_len).with_instruction(self.index))
+            }
+            State::Body => {
+                let end = self.sections.iter().flatten().map(|entry| entry.end()).max().unwrap_or(0);
+                if end > self.offset as u64 {
+                    return Err(truncated((end - self.offset as u64) as usize, 0));
+                }
+                match self.checksum {
+                    Some(expected) if expected != self.crc.finish() => Err(crate::Error::at(
+                        HEADER_SIZE,
+                        ErrorKind::ChecksumMismatch {
+                            expected,
+                            actual: self.crc.finish(),
+                        },
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            State::Failed(e) => Err(e),
+        }
+    }
+
+    fn fail(&mut self, e: crate::Error) -> crate::Error {
+        self.state = State::Failed(e);
+        e
+    }
+
+    fn section(&self, ty: SectionType) -> Option<SectionEntry> {
+        self.sections[ty.index()?]
+    }
+
+    fn text_len(&self) -> usize {
+        self.section(SectionType::TEXT).map(|entry| entry.len as usize).unwrap_or(0)
+    }
+
+    /// Records a section table entry. `entry_offset` is the offset of the entry in the file.
+    fn add_section(&mut self, entry: SectionEntry, entry_offset: usize) -> Result<()> {
+        let table_end = self.header.map(|header| header.table_end()).unwrap_or(0);
+        // The length of the file isn't known yet, truncation is reported by `finish`
+        entry
+            .check_bounds(table_end, u64::MAX)
+            .map_err(|e| e.with_offset(entry_offset))?;
+        SectionTable::add_known(&mut self.sections, entry).map_err(|e| e.with_offset(entry_offset))
+    }
+
+    /// Called once the whole section table has been read.
+    fn finish_table(&mut self) -> Result<()> {
+        let table_start = self.header.map(|header| header.table_start()).unwrap_or(0);
+        for ty in [SectionType::TEXT, SectionType::CODE] {
+            if self.section(ty).is_none() {
+                return Err(crate::Error::at(table_start, ErrorKind::MissingSection { ty: ty.0 }));
+            }
+        }
+        self.state = State::Body;
+        Ok(())
+    }
+
+    /// Validates a fragment of the data section, which starts at `offset` in the file. Errors
+    /// are at the start of the first invalid character, like those of [`core::str::from_utf8`].
+    fn check_text(&mut self, mut bytes: &[u8], mut offset: usize, utf8: bool) -> Result<()> {
+        if !utf8 {
+            if let Some(pos) = bytes.iter().position(|byte| !byte.is_ascii()) {
+                return Err(crate::Error::at(offset + pos, ErrorKind::NonAsciiText { byte: bytes[pos] }));
+            }
+            return Ok(());
+        }
+        if self.utf8_pending_len > 0 {
+            // Finish the character the previous fragment ended in
+            let pending_len = self.utf8_pending_len;
+            let pending_offset = offset - pending_len;
+            let take = bytes.len().min(4 - pending_len);
+            let mut buf = self.utf8_pending;
+            buf[pending_len..pending_len + take].copy_from_slice(&bytes[..take]);
+            let valid = match core::str::from_utf8(&buf[..pending_len + take]) {
+                Ok(text) => text,
+                Err(e) if e.valid_up_to() > 0 => core::str::from_utf8(&buf[..e.valid_up_to()]).unwrap(),
+                Err(e) if e.error_len().is_none() => {
+                    // Still incomplete, so this fragment was too short to finish it
+                    self.utf8_pending = buf;
+                    self.utf8_pending_len += take;
+                    return Ok(());
+                }
+                Err(_) => return Err(crate::Error::at(pending_offset, ErrorKind::InvalidUtf8)),
+            };
+            let char_len = valid.chars().next().map_or(0, char::len_utf8);
+            self.utf8_pending_len = 0;
+            bytes = &bytes[char_len - pending_len..];
+            offset += char_len - pending_len;
+        }
+        match core::str::from_utf8(bytes) {
+            Ok(_) => Ok(()),
+            // A character that continues in the next fragment
+            Err(e) if e.error_len().is_none() => {
+                let rest = &bytes[e.valid_up_to()..];
+                self.utf8_pending[..rest.len()].copy_from_slice(rest);
+                self.utf8_pending_len = rest.len();
+                Ok(())
+            }
+            Err(e) => Err(crate::Error::at(offset + e.valid_up_to(), ErrorKind::InvalidUtf8)),
+        }
+    }
+
+    /// Tries to decode an instruction from the buffered bytes.
+    fn decode_buffered(&mut self, encoding: CodeEncoding) -> Result<(Instruction, usize)> {
+        let bytes = &self.buf[..self.buf_len];
+        let (binary, len) = match encoding {
+            CodeEncoding::Fixed => {
+                if bytes.len() < BinaryInstruction::ENCODED_SIZE {
+                    return Err(crate::Error::new(ErrorKind::Truncated {
+                        needed: BinaryInstruction::ENCODED_SIZE,
+                        available: bytes.len(),
+                    }));
+                }
+                let mut arr: [u8; 9] = [0, 0, 0, 0, 0, 0, 0, 0, 0];
+                arr.copy_from_slice(&bytes[..BinaryInstruction::ENCODED_SIZE]);
+                (BinaryInstruction::try_from(arr)?, BinaryInstruction::ENCODED_SIZE)
+            }
+            CodeEncoding::Compact => {
+                // Don't let a failed attempt on incomplete input advance the delta state
+                let mut compact = self.compact.clone();
+                let decoded = compact.decode(bytes)?;
+                self.compact = compact;
+                decoded
+            }
+        };
+        let instruction = Instruction::try_from(binary)?;
+        if let Instruction::Text(range) = instruction {
+            if range.base.0 as u64 + range.range as u64 > self.text_len() as u64 {
+                return Err(crate::Error::new(ErrorKind::TextOutOfBounds {
+                    base: range.base.0,
+                    range: range.range,
+                    text_len: self.text_len(),
+                }));
+            }
+        }
+        Ok((instruction, len))
+    }
+}
+
+/// Events decoded from one chunk, see [`StreamDecoder::feed`].
+pub struct Events<'d, 'a> {
+    decoder: &'d mut StreamDecoder,
+    chunk: &'a [u8],
+    pos: usize,
+}
+
+impl<'d, 'a> Events<'d, 'a> {
+    fn remaining(&self) -> &'a [u8] {
+        &self.chunk[self.pos..]
+    }
+
+    fn consume(&mut self, len: usize) {
+        // The stored checksum is the only part of the file that isn't covered by it
+        if !matches!(self.decoder.state, State::Checksum) {
+            self.decoder.crc.update(&self.chunk[self.pos..self.pos + len]);
+        }
+        self.pos += len;
+        self.decoder.offset += len;
+    }
+
+    /// Moves input into the buffer until it holds `len` bytes. Returns whether it does.
+    fn fill(&mut self, len: usize) -> bool {
+        let decoder = &mut *self.decoder;
+        let take = (len - decoder.buf_len).min(self.chunk.len() - self.pos);
+        decoder.buf[decoder.buf_len..decoder.buf_len + take]
+            .copy_from_slice(&self.chunk[self.pos..self.pos + take]);
+        decoder.buf_len += take;
+        self.consume(take);
+        self.decoder.buf_len == len
+    }
+
+    fn next_header(&mut self) -> Option<Result<Event<'a>>> {
+        if !self.fill(HEADER_SIZE) {
+            // Report garbage as early as possible, instead of waiting for a whole header
+            let magic_len = self.decoder.buf_len.min(MAGIC.len());
+            if self.decoder.buf[..magic_len] != MAGIC[..magic_len] {
+                let e = crate::Error::at(0, ErrorKind::InvalidMagic);
+                return Some(Err(self.decoder.fail(e)));
+            }
+            return None;
+        }
+        let header = match Header::try_from(&self.decoder.buf[..HEADER_SIZE]) {
+            Ok(header) => header,
+            Err(e) => return Some(Err(self.decoder.fail(e))),
+        };
+        if header.flags.contains(Flags::COMPRESSED_TEXT) {
+            let e = crate::Error::at(6, ErrorKind::UnsupportedFlags { flags: Flags::COMPRESSED_TEXT.0 });
+            return Some(Err(self.decoder.fail(e)));
+        }
+        self.decoder.header = Some(header);
+        self.decoder.buf_len = 0;
+        self.decoder.table_left = header.section_count;
+        self.decoder.state = if header.flags.contains(Flags::CHECKSUM) {
+            State::Checksum
+        } else {
+            State::Table
+        };
+        Some(Ok(Event::Header(header)))
+    }
+
+    fn next_checksum(&mut self) -> Option<Result<Event<'a>>> {
+        if !self.fill(CHECKSUM_SIZE) {
+            return None;
+        }
+        let mut stored = [0; CHECKSUM_SIZE];
+        stored.copy_from_slice(&self.decoder.buf[..CHECKSUM_SIZE]);
+        self.decoder.checksum = Some(u32::from_le_bytes(stored));
+        self.decoder.buf_len = 0;
+        self.decoder.state = State::Table;
+        self.next()
+    }
+
+    fn next_table_entry(&mut self) -> Option<Result<Event<'a>>> {
+        if self.decoder.table_left == 0 {
+            if let Err(e) = self.decoder.finish_table() {
+                return Some(Err(self.decoder.fail(e)));
+            }
+            return self.next();
+        }
+        if !self.fill(SECTION_ENTRY_SIZE) {
+            return None;
+        }
+        let mut bytes = [0; SECTION_ENTRY_SIZE];
+        bytes.copy_from_slice(&self.decoder.buf[..SECTION_ENTRY_SIZE]);
+        self.decoder.buf_len = 0;
+        self.decoder.table_left -= 1;
+        let entry_offset = self.decoder.offset - SECTION_ENTRY_SIZE;
+        if let Err(e) = self.decoder.add_section(SectionEntry::from_bytes(bytes), entry_offset) {
+            return Some(Err(self.decoder.fail(e)));
+        }
+        self.next()
+    }
+
+    fn next_body(&mut self) -> Option<Result<Event<'a>>> {
+        let pos = self.decoder.offset as u64;
+        let contains = |entry: Option<SectionEntry>| {
+            entry.filter(|entry| entry.offset as u64 <= pos && pos < entry.end())
+        };
+        // A partial instruction may reach up to the end of the code section
+        if self.decoder.buf_len > 0 {
+            return self.next_instruction(self.decoder.section(SectionType::CODE)?);
+        }
+        if let Some(text) = contains(self.decoder.section(SectionType::TEXT)) {
+            return self.next_text(text);
+        }
+        if let Some(code) = contains(self.decoder.section(SectionType::CODE)) {
+            return self.next_instruction(code);
+        }
+        if let Some(section) = self.decoder.sections.iter().find_map(|entry| contains(*entry)) {
+            let take = ((section.end() - pos) as usize).min(self.remaining().len());
+            if take == 0 {
+                return None;
+            }
+            let bytes = &self.remaining()[..take];
+            let offset = self.decoder.offset - section.offset as usize;
+            self.consume(take);
+            return Some(Ok(Event::Section {
+                ty: section.ty,
+                offset,
+                bytes,
+            }));
+        }
+        // Skip to the next known section, or past everything if there is none
+        let next_start = self
+            .decoder
+            .sections
+            .iter()
+            .flatten()
+            .map(|entry| entry.offset as u64)
+            .filter(|start| *start > pos)
+            .min()
+            .unwrap_or(u64::MAX);
+        let take = (next_start - pos).min(self.remaining().len() as u64) as usize;
+        if take == 0 {
+            return None;
+        }
+        self.consume(take);
+        self.next()
+    }
+
+    fn next_text(&mut self, section: SectionEntry) -> Option<Result<Event<'a>>> {
+        let header = self.decoder.header?;
+        let left = (section.end() - self.decoder.offset as u64) as usize;
+        let take = left.min(self.remaining().len());
+        if take == 0 {
+            return None;
+        }
+        let bytes = &self.remaining()[..take];
+        let utf8 = header.flags.contains(Flags::UTF8_TEXT);
+        if let Err(e) = self.decoder.check_text(bytes, self.decoder.offset, utf8) {
+            return Some(Err(self.decoder.fail(e)));
+        }
+        if take == left && self.decoder.utf8_pending_len != 0 {
+            // The section ends in the middle of a character
+            let pending_offset = self.decoder.offset + take - self.decoder.utf8_pending_len;
+            let e = crate::Error::at(pending_offset, ErrorKind::InvalidUtf8);
+            return Some(Err(self.decoder.fail(e)));
+        }
+        let offset = self.decoder.offset - section.offset as usize;
+        self.consume(take);
+        Some(Ok(Event::Text { offset, bytes }))
+    }
+
+    fn next_instruction(&mut self, section: SectionEntry) -> Option<Result<Event<'a>>> {
+        let header = self.decoder.header?;
+        let encoding = if header.flags.contains(Flags::COMPACT_CODE) {
+            CodeEncoding::Compact
+        } else {
+            CodeEncoding::Fixed
+        };
+        let left = (section.end() - self.decoder.offset as u64) as usize;
+        let decoder = &mut *self.decoder;
+        let old_len = decoder.buf_len;
+        let take = (MAX_INSTRUCTION_SIZE - old_len).min(self.chunk.len() - self.pos).min(left);
+        if take == 0 && old_len == 0 {
+            return None;
+        }
+        decoder.buf[old_len..old_len + take].copy_from_slice(&self.chunk[self.pos..self.pos + take]);
+        decoder.buf_len += take;
+        match decoder.decode_buffered(encoding) {
+            Ok((instruction, len)) => {
+                // Only consume the bytes of this instruction, the rest is decoded from the chunk again
+                decoder.buf_len = 0;
+                decoder.index += 1;
+                self.consume(len - old_len);
+                Some(Ok(Event::Instruction(instruction)))
+            }
+            // Keep the partial instruction until more input arrives, unless the section ends here
+            Err(e) if matches!(e.kind, ErrorKind::Truncated { .. }) && take < left => {
+                self.consume(take);
+                None
+            }
+            Err(e) => {
+                let e = e.with_offset(decoder.offset - old_len).with_instruction(decoder.index);
+                Some(Err(decoder.fail(e)))
+            }
+        }
+    }
+}
+
+impl<'d, 'a> Iterator for Events<'d, 'a> {
+    type Item = Result<Event<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.decoder.state {
+            State::Header => self.next_header(),
+            State::Checksum => self.next_checksum(),
+            State::Table => self.next_table_entry(),
+            State::Body => self.next_body(),
+            State::Failed(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[cfg(all(feature = "alloc", not(feature = "std")))]
+    use alloc::{string::String, vec, vec::Vec};
+
+    /// `Hello` in bold, with compact code, encoded by hand so it can be decoded without `alloc`
+    const HELLO: [u8; 49] = [
+        // Magic, version 2, compact code and 2 sections
+        b'S', b'W', b'B', 0, 2, 0, 2, 0, 2, 0, 0, 0,
+        // The text section at 36, 5 bytes long, and the code section at 41, 8 bytes long
+        1, 0, 0, 0, 36, 0, 0, 0, 5, 0, 0, 0,
+        2, 0, 0, 0, 41, 0, 0, 0, 8, 0, 0, 0,
+        b'H', b'e', b'l', b'l', b'o',
+        // Push bold, text 0+5, pop bold, stop
+        0x02, 0x01, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00,
+    ];
+
+    const HELLO_CODE: [Instruction; 4] = [
+        Instruction::Push(StyleVar::Bold),
+        Instruction::Text(AddressRange {
+            base: Address(0),
+            range: 5,
+        }),
+        Instruction::Pop(StyleVar::Bold),
+        Instruction::Stop,
+    ];
+
+    #[test]
+    fn test_stream_byte_by_byte() {
+        let mut decoder = StreamDecoder::new();
+        let mut text = [0; 5];
+        let mut code = 0;
+        for chunk in HELLO.chunks(1) {
+            for event in decoder.feed(chunk) {
+                match event.unwrap() {
+                    Event::Header(header) => assert_eq!(header.section_count, 2),
+                    Event::Text { offset, bytes } => {
+                        text[offset..offset + bytes.len()].copy_from_slice(bytes);
+                    }
+                    Event::Instruction(instruction) => {
+                        assert_eq!(instruction, HELLO_CODE[code]);
+                        code += 1;
+                    }
+                    Event::Section { .. } => panic!("unexpected section"),
+                }
+            }
+        }
+        decoder.finish().unwrap();
+        assert_eq!(&text, b"Hello");
+        assert_eq!(code, HELLO_CODE.len());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_stream_in_small_chunks() {
+        let program = Program {
+            text: Text::from(String::from("Zoë says hello")),
+            code: vec![
+                Instruction::Push(StyleVar::Bold),
+                Instruction::Text(AddressRange {
+                    base: Address(0),
+                    range: 4,
+                }),
+                Instruction::Pop(StyleVar::Bold),
+                Instruction::Stop,
+            ],
+            meta: Metadata {
+                title: Some(String::from("Greetings")),
+                ..Default::default()
+            },
+            // Skipped by the decoder
+            sections: vec![Section {
+                ty: SectionType(0xf00),
+                data: vec![0xff; 5],
+            }],
+            ..Default::default()
+        };
+        for encoding in [CodeEncoding::Fixed, CodeEncoding::Compact] {
+            let bytes = program
+                .clone()
+                .to_binary()
+                .with_encoding(encoding)
+                .with_checksum(true)
+                .into_byte_buffer();
+            let mut decoder = StreamDecoder::new();
+            let mut text = Vec::new();
+            let mut code = Vec::new();
+            let mut meta = Vec::new();
+            for chunk in bytes.chunks(3) {
+                for event in decoder.feed(chunk) {
+                    match event.unwrap() {
+                        Event::Header(_) => {}
+                        Event::Text { offset, bytes } => {
+                            assert_eq!(offset, text.len());
+                            text.extend_from_slice(bytes);
+                        }
+                        Event::Instruction(instruction) => code.push(instruction),
+                        Event::Section { ty, offset, bytes } => {
+                            assert_eq!(ty, SectionType::META);
+                            assert_eq!(offset, meta.len());
+                            meta.extend_from_slice(bytes);
+                        }
+                    }
+                }
+            }
+            decoder.finish().unwrap();
+            assert_eq!(text, program.text.as_bytes());
+            assert_eq!(code, program.code);
+            assert_eq!(MetadataView::decode(&meta).unwrap().title, Some("Greetings"));
+
+            let mut truncated = StreamDecoder::new();
+            for event in truncated.feed(&bytes[..bytes.len() - 2]) {
+                event.unwrap();
+            }
+            assert!(truncated.finish().is_err());
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_stream_invalid_utf8() {
+        let program = Program {
+            text: Text::from(String::from("Zoë says hello")),
+            code: vec![Instruction::Stop],
+            ..Default::default()
+        };
+        let text = program.text.as_bytes().to_vec();
+        let bytes = program.to_binary().into_byte_buffer();
+        let text_start = bytes.windows(text.len()).position(|window| window == text).unwrap();
+        // An overlong encoding of `A` in place of `ë`, and a surrogate in place of `hel`, which
+        // both have the structure of UTF-8
+        for (pos, replacement) in [(2, &[0xc1, 0x81][..]), (10, &[0xed, 0xa0, 0x80][..])] {
+            let mut corrupt = bytes.clone();
+            let start = text_start + pos;
+            corrupt[start..start + replacement.len()].copy_from_slice(replacement);
+            // Also split the invalid character across chunks
+            for chunk_size in [1, 2, corrupt.len()] {
+                let mut decoder = StreamDecoder::new();
+                let err = corrupt
+                    .chunks(chunk_size)
+                    .flat_map(|chunk| decoder.feed(chunk).collect::<Vec<_>>())
+                    .find_map(|event| event.err())
+                    .unwrap();
+                assert_eq!(err.kind, ErrorKind::InvalidUtf8);
+                assert_eq!(err.offset, Some(start));
+            }
+        }
+    }
+}
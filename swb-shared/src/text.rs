@@ -0,0 +1,131 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use ascii::{AsciiChar, AsciiString, IntoAsciiString};
+
+use crate::view;
+use crate::{AddressRange, ErrorKind, Result};
+
+/// Contents of the data section. Files are ascii unless the [`crate::Flags::UTF8_TEXT`] header
+/// flag is set. In both cases `Instruction::Text` ranges are byte ranges into this buffer.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Text {
+    Ascii(AsciiString),
+    Utf8(String),
+}
+
+impl Text {
+    /// Decodes a data section. Offsets in errors are relative to the start of `bytes`.
+    pub fn decode(bytes: &[u8], utf8: bool) -> Result<Self> {
+        let mut vec = Vec::with_capacity(bytes.len());
+        vec.extend_from_slice(bytes);
+        if utf8 {
+            let text = String::from_utf8(vec).map_err(|e| {
+                let pos = e.utf8_error().valid_up_to();
+                crate::Error::at(pos, ErrorKind::InvalidUtf8)
+            })?;
+            Ok(Text::Utf8(text))
+        } else {
+            let text = vec.into_ascii_string().map_err(|e| {
+                let pos = e.ascii_error().valid_up_to();
+                crate::Error::at(pos, ErrorKind::NonAsciiText { byte: bytes[pos] })
+            })?;
+            Ok(Text::Ascii(text))
+        }
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        matches!(self, Text::Utf8(_))
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Text::Ascii(text) => text.as_bytes(),
+            Text::Utf8(text) => text.as_bytes(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Text::Ascii(text) => text.as_str(),
+            Text::Utf8(text) => text.as_str(),
+        }
+    }
+
+    /// Returns the text a `Text` instruction refers to, or `None` if the range is out of
+    /// bounds or does not fall on character boundaries.
+    pub fn get(&self, range: AddressRange) -> Option<&str> {
+        let start = range.base.0 as usize;
+        let end = start.checked_add(range.range as usize)?;
+        self.as_str().get(start..end)
+    }
+
+    /// Checks that `range` can be resolved with [`Text::get`].
+    pub fn check_range(&self, range: AddressRange) -> Result<()> {
+        view::check_range(self.as_str(), range)
+    }
+
+    /// Converts the text to ascii, replacing every non-ascii character by `replacement`.
+    /// Because a multi-byte character becomes a single byte, the second value maps every
+    /// character boundary of the original text to its offset in the converted text.
+    pub fn to_ascii_lossy(&self, replacement: AsciiChar) -> (AsciiString, Vec<u32>) {
+        let mut result = AsciiString::with_capacity(self.len());
+        let mut offsets = Vec::with_capacity(self.len() + 1);
+        for c in self.as_str().chars() {
+            let new_offset = result.len() as u32;
+            // Every byte of the original character maps to the start of its replacement
+            for _ in 0..c.len_utf8() {
+                offsets.push(new_offset);
+            }
+            result.push(AsciiChar::from_ascii(c).unwrap_or(replacement));
+        }
+        offsets.push(result.len() as u32);
+        (result, offsets)
+    }
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Text::Ascii(AsciiString::new())
+    }
+}
+
+impl From<AsciiString> for Text {
+    fn from(value: AsciiString) -> Self {
+        Text::Ascii(value)
+    }
+}
+
+impl From<String> for Text {
+    /// Stores the text as ascii when possible, so the result stays readable on ascii-only devices.
+    fn from(value: String) -> Self {
+        match value.into_ascii_string() {
+            Ok(ascii) => Text::Ascii(ascii),
+            Err(e) => Text::Utf8(e.into_source()),
+        }
+    }
+}
+
+/// Splits text into chunks of at most `max` bytes, never splitting a character.
+pub(crate) fn blocks(text: &str, max: usize) -> impl Iterator<Item = (usize, &str)> {
+    let mut cur = 0;
+    core::iter::from_fn(move || {
+        if cur >= text.len() {
+            return None;
+        }
+        let mut end = (cur + max).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let start = cur;
+        cur = end;
+        text.get(start..end).map(|block| (start, block))
+    })
+}
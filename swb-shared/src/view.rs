@@ -0,0 +1,510 @@
+#[cfg(not(feature = "std"))]
+use core::str;
+#[cfg(feature = "std")]
+use std::str;
+
+#[cfg(not(feature = "std"))]
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::convert::TryInto;
+
+use crate::compression;
+use crate::{
+    AddressRange, AnchorTable, BinaryInstruction, CodeEncoding, CompactDecoder, ControlKind, Crc32,
+    ErrorKind, Flags, FormRecord, FormTable, Header, ImageTable, Instruction, LinkTable, MetadataView, Result, SectionEntry,
+    SectionTable, SectionType, CHECKSUM_SIZE, HEADER_SIZE, SECTION_ENTRY_SIZE,
+};
+
+/// A program borrowed from an encoded buffer. The header, section table and data section
+/// are validated when the view is created, instructions are decoded lazily by
+/// [`ProgramView::instructions`]. Nothing here allocates, so this is usable without the
+/// `alloc` feature.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramView<'a> {
+    header: Header,
+    bytes: &'a [u8],
+    table: &'a [u8],
+    text: &'a str,
+    code: &'a [u8],
+    code_offset: usize,
+    links: &'a [u8],
+    link_count: usize,
+    images: &'a [u8],
+    image_count: usize,
+    forms: &'a [u8],
+    control_count: usize,
+    anchors: &'a [u8],
+    anchor_count: usize,
+}
+
+/// The result of validating the header, checksum and section table of a buffer.
+struct Layout<'a> {
+    header: Header,
+    table: &'a [u8],
+    text: SectionEntry,
+    code: SectionEntry,
+}
+
+impl<'a> Layout<'a> {
+    fn raw_text(&self, bytes: &'a [u8]) -> &'a [u8] {
+        &bytes[self.text.offset as usize..self.text.end() as usize]
+    }
+}
+
+/// Validates the header, checksum and section table, and finds the data and code sections.
+fn parse(bytes: &[u8]) -> Result<Layout<'_>> {
+    let header = Header::try_from(bytes)?;
+    let table_start = header.table_start();
+    if header.flags.contains(Flags::CHECKSUM) {
+        let stored = bytes.get(HEADER_SIZE..table_start).ok_or(crate::Error::at(
+            HEADER_SIZE,
+            ErrorKind::Truncated {
+                needed: CHECKSUM_SIZE,
+                available: bytes.len() - HEADER_SIZE,
+            },
+        ))?;
+        let expected = u32::from_le_bytes(stored.try_into().unwrap());
+        let mut crc = Crc32::new();
+        crc.update(&bytes[..HEADER_SIZE]);
+        crc.update(&bytes[table_start..]);
+        let actual = crc.finish();
+        if expected != actual {
+            return Err(crate::Error::at(HEADER_SIZE, ErrorKind::ChecksumMismatch { expected, actual }));
+        }
+    }
+    let table_end = header.table_end();
+    if table_end > bytes.len() as u64 {
+        return Err(crate::Error::at(
+            table_start,
+            ErrorKind::Truncated {
+                needed: (table_end - table_start as u64) as usize,
+                available: bytes.len() - table_start,
+            },
+        ));
+    }
+    let table = &bytes[table_start..table_end as usize];
+
+    let mut known = [None; SectionType::KNOWN_COUNT];
+    for (i, entry) in SectionTable::new(table).enumerate() {
+        let entry_offset = table_start + i * SECTION_ENTRY_SIZE;
+        entry
+            .check_bounds(table_end, bytes.len() as u64)
+            .map_err(|e| e.with_offset(entry_offset))?;
+        SectionTable::add_known(&mut known, entry).map_err(|e| e.with_offset(entry_offset))?;
+    }
+    let require = |ty: SectionType| {
+        known[ty.index().unwrap()].ok_or(crate::Error::at(table_start, ErrorKind::MissingSection { ty: ty.0 }))
+    };
+    Ok(Layout {
+        header,
+        table,
+        text: require(SectionType::TEXT)?,
+        code: require(SectionType::CODE)?,
+    })
+}
+
+/// Validates a decoded data section. Offsets in errors are relative to the start of `bytes`.
+fn decode_text(bytes: &[u8], utf8: bool) -> Result<&str> {
+    if !utf8 {
+        if let Some(pos) = bytes.iter().position(|byte| !byte.is_ascii()) {
+            return Err(crate::Error::at(pos, ErrorKind::NonAsciiText { byte: bytes[pos] }));
+        }
+    }
+    str::from_utf8(bytes).map_err(|e| crate::Error::at(e.valid_up_to(), ErrorKind::InvalidUtf8))
+}
+
+/// Checks that a `Text` instruction refers to whole characters inside `text`.
+pub(crate) fn check_range(text: &str, range: AddressRange) -> Result<()> {
+    let AddressRange { base, range: len } = range;
+    if base.0 as u64 + len as u64 > text.len() as u64 {
+        return Err(crate::Error::new(ErrorKind::TextOutOfBounds {
+            base: base.0,
+            range: len,
+            text_len: text.len(),
+        }));
+    }
+    let start = base.0 as usize;
+    if text.get(start..start + len as usize).is_none() {
+        return Err(crate::Error::new(ErrorKind::TextSplitsCharacter {
+            base: base.0,
+            range: len,
+        }));
+    }
+    Ok(())
+}
+
+impl<'a> ProgramView<'a> {
+    /// Creates a view over an encoded program. Fails with [`ErrorKind::ScratchTooSmall`] if the
+    /// data section is compressed, use [`ProgramView::with_scratch`] for those.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        Self::with_scratch(bytes, &mut [])
+    }
+
+    /// Number of bytes of scratch space needed to view this program, which is zero unless
+    /// the data section is compressed.
+    pub fn scratch_len(bytes: &[u8]) -> Result<usize> {
+        let layout = parse(bytes)?;
+        if layout.header.flags.contains(Flags::COMPRESSED_TEXT) {
+            compression::decompressed_len(layout.raw_text(bytes))
+                .map_err(|e| e.with_offset(layout.text.offset as usize))
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Creates a view over an encoded program, decompressing the data section into `scratch`
+    /// if needed. See [`ProgramView::scratch_len`] for the required size.
+    pub fn with_scratch(bytes: &'a [u8], scratch: &'a mut [u8]) -> Result<Self> {
+        let layout = parse(bytes)?;
+        let header = layout.header;
+        let text_offset = layout.text.offset as usize;
+        let text = layout.raw_text(bytes);
+        let utf8 = header.flags.contains(Flags::UTF8_TEXT);
+        let text = if header.flags.contains(Flags::COMPRESSED_TEXT) {
+            let decompressed =
+                compression::decompress(text, scratch).map_err(|e| e.with_offset(text_offset))?;
+            // Offsets into decompressed text don't map onto the file, so they are dropped
+            decode_text(decompressed, utf8).map_err(|e| crate::Error::at(text_offset, e.kind))?
+        } else {
+            decode_text(text, utf8).map_err(|e| e.with_offset(text_offset))?
+        };
+        let code_offset = layout.code.offset as usize;
+        let mut view = Self {
+            header,
+            bytes,
+            table: layout.table,
+            text,
+            code: &bytes[code_offset..layout.code.end() as usize],
+            code_offset,
+            links: &[],
+            link_count: 0,
+            images: &[],
+            image_count: 0,
+            forms: &[],
+            control_count: 0,
+            anchors: &[],
+            anchor_count: 0,
+        };
+        // Validate these tables up front, so instructions can be checked against them
+        (view.links, view.link_count) = view.validate_table(SectionType::LINKS, LinkTable::new)?;
+        (view.images, view.image_count) = view.validate_table(SectionType::IMAGES, ImageTable::new)?;
+        (view.anchors, view.anchor_count) = view.validate_table(SectionType::ANCHORS, AnchorTable::new)?;
+        if let Some(entry) = view.sections().find(|entry| entry.ty == SectionType::FORMS) {
+            view.forms = view.section_bytes(entry);
+            view.control_count = view.forms().validate().map_err(|e| e.with_offset(entry.offset as usize))?;
+        }
+        Ok(view)
+    }
+
+    /// Decodes every entry of a table section, returning its contents and number of entries.
+    fn validate_table<T, I>(
+        &self,
+        ty: SectionType,
+        table: impl Fn(&'a [u8]) -> I,
+    ) -> Result<(&'a [u8], usize)>
+    where
+        I: Iterator<Item = Result<T>>,
+    {
+        let Some(entry) = self.sections().find(|entry| entry.ty == ty) else {
+            return Ok((&[], 0));
+        };
+        let bytes = self.section_bytes(entry);
+        let mut count = 0;
+        for item in table(bytes) {
+            item.map_err(|e| e.with_offset(entry.offset as usize))?;
+            count += 1;
+        }
+        Ok((bytes, count))
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        self.header.flags.contains(Flags::UTF8_TEXT)
+    }
+
+    pub fn encoding(&self) -> CodeEncoding {
+        if self.header.flags.contains(Flags::COMPACT_CODE) {
+            CodeEncoding::Compact
+        } else {
+            CodeEncoding::Fixed
+        }
+    }
+
+    /// The whole data section
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Returns the text a `Text` instruction refers to, or `None` if the range is invalid.
+    pub fn get(&self, range: AddressRange) -> Option<&'a str> {
+        let start = range.base.0 as usize;
+        let end = start.checked_add(range.range as usize)?;
+        self.text.get(start..end)
+    }
+
+    /// All entries of the section table, including the ones this crate doesn't know.
+    pub fn sections(&self) -> SectionTable<'a> {
+        SectionTable::new(self.table)
+    }
+
+    /// The raw contents of the first section of type `ty`, if there is one.
+    pub fn section(&self, ty: SectionType) -> Option<&'a [u8]> {
+        let entry = self.sections().find(|entry| entry.ty == ty)?;
+        Some(self.section_bytes(entry))
+    }
+
+    /// The metadata of the program, which is empty if it has no metadata section.
+    pub fn metadata(&self) -> Result<MetadataView<'a>> {
+        match self.sections().find(|entry| entry.ty == SectionType::META) {
+            Some(entry) => MetadataView::decode(self.section_bytes(entry))
+                .map_err(|e| e.with_offset(entry.offset as usize)),
+            None => Ok(MetadataView::default()),
+        }
+    }
+
+    /// The link table, which is empty if the program has no links.
+    pub fn links(&self) -> LinkTable<'a> {
+        LinkTable::new(self.links)
+    }
+
+    /// The image table, which is empty if the program has no images.
+    pub fn images(&self) -> ImageTable<'a> {
+        ImageTable::new(self.images)
+    }
+
+    /// The form table, which is empty if the program has no forms.
+    pub fn forms(&self) -> FormTable<'a> {
+        FormTable::new(self.forms)
+    }
+
+    /// The anchor table, which is empty if the program has no anchors.
+    pub fn anchors(&self) -> AnchorTable<'a> {
+        AnchorTable::new(self.anchors)
+    }
+
+    /// The raw contents of an entry returned by [`ProgramView::sections`].
+    pub fn section_bytes(&self, entry: SectionEntry) -> &'a [u8] {
+        // Bounds were checked when the view was created
+        &self.bytes[entry.offset as usize..entry.end() as usize]
+    }
+
+    pub fn instructions(&self) -> Instructions<'a> {
+        Instructions {
+            text: self.text,
+            code: self.code,
+            code_offset: self.code_offset,
+            link_count: self.link_count,
+            image_count: self.image_count,
+            forms: self.forms,
+            controls: FormTable::new(self.forms),
+            next_control: 0,
+            control_count: self.control_count,
+            anchor_count: self.anchor_count,
+            encoding: self.encoding(),
+            compact: CompactDecoder::new(),
+            offset: 0,
+            index: 0,
+        }
+    }
+}
+
+/// Lazily decodes and validates the instructions of a [`ProgramView`].
+/// Iteration stops after the first error.
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    text: &'a str,
+    code: &'a [u8],
+    code_offset: usize,
+    link_count: usize,
+    image_count: usize,
+    forms: &'a [u8],
+    // Where the last control lookup stopped, and the index of the control it returns next
+    controls: FormTable<'a>,
+    next_control: u32,
+    control_count: usize,
+    anchor_count: usize,
+    encoding: CodeEncoding,
+    compact: CompactDecoder,
+    offset: usize,
+    index: usize,
+}
+
+impl<'a> Instructions<'a> {
+    /// Byte offset of the next instruction, relative to the start of the encoded program.
+    pub fn offset(&self) -> usize {
+        self.code_offset + self.offset
+    }
+
+    /// Returns the kind of a control. Controls are usually shown in the order of their index, so
+    /// the search goes on from the previous control instead of starting over every time.
+    fn control_kind(&mut self, index: u32) -> Option<ControlKind> {
+        if index < self.next_control {
+            self.controls = FormTable::new(self.forms);
+            self.next_control = 0;
+        }
+        // The form table was validated by the view, so every record can be decoded
+        for record in self.controls.by_ref() {
+            if let Ok(FormRecord::Control(control)) = record {
+                self.next_control += 1;
+                if self.next_control == index + 1 {
+                    return Some(control.kind);
+                }
+            }
+        }
+        None
+    }
+
+    fn decode_next(&mut self) -> Result<Instruction> {
+        let remaining = &self.code[self.offset..];
+        let (binary, len) = match self.encoding {
+            CodeEncoding::Fixed => {
+                let chunk = remaining.get(..BinaryInstruction::ENCODED_SIZE).ok_or(
+                    crate::Error::new(ErrorKind::Truncated {
+                        needed: BinaryInstruction::ENCODED_SIZE,
+                        available: remaining.len(),
+                    }),
+                )?;
+                let mut arr: [u8; 9] = [0, 0, 0, 0, 0, 0, 0, 0, 0];
+                arr.copy_from_slice(chunk);
+                (BinaryInstruction::try_from(arr)?, BinaryInstruction::ENCODED_SIZE)
+            }
+            CodeEncoding::Compact => self.compact.decode(remaining)?,
+        };
+        let instruction = Instruction::try_from(binary)?;
+        match instruction {
+            Instruction::Text(range) => check_range(self.text, range)?,
+            Instruction::LinkStart(index) if index as usize >= self.link_count => {
+                return Err(crate::Error::new(ErrorKind::LinkOutOfBounds {
+                    index,
+                    count: self.link_count,
+                }));
+            }
+            Instruction::Image(index) if index as usize >= self.image_count => {
+                return Err(crate::Error::new(ErrorKind::ImageOutOfBounds {
+                    index,
+                    count: self.image_count,
+                }));
+            }
+            Instruction::Anchor(index) if index as usize >= self.anchor_count => {
+                return Err(crate::Error::new(ErrorKind::AnchorOutOfBounds {
+                    index,
+                    count: self.anchor_count,
+                }));
+            }
+            _ => {}
+        }
+        if let Some((kind, index)) = instruction.control() {
+            if index as usize >= self.control_count {
+                return Err(crate::Error::new(ErrorKind::ControlOutOfBounds {
+                    index,
+                    count: self.control_count,
+                }));
+            }
+            if self.control_kind(index) != Some(kind) {
+                return Err(crate::Error::new(ErrorKind::ControlKindMismatch { index }));
+            }
+        }
+        self.offset += len;
+        Ok(instruction)
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.code.len() {
+            return None;
+        }
+        let result = self
+            .decode_next()
+            .map_err(|e| e.with_offset(self.offset()).with_instruction(self.index));
+        if result.is_err() {
+            self.offset = self.code.len();
+        }
+        self.index += 1;
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[cfg(all(feature = "alloc", not(feature = "std")))]
+    use alloc::{string::String, vec};
+
+    /// `Hello` in bold, with compact code, encoded by hand so it can be decoded without `alloc`
+    const HELLO: [u8; 49] = [
+        // Magic, version 2, compact code and 2 sections
+        b'S', b'W', b'B', 0, 2, 0, 2, 0, 2, 0, 0, 0,
+        // The text section at 36, 5 bytes long, and the code section at 41, 8 bytes long
+        1, 0, 0, 0, 36, 0, 0, 0, 5, 0, 0, 0,
+        2, 0, 0, 0, 41, 0, 0, 0, 8, 0, 0, 0,
+        b'H', b'e', b'l', b'l', b'o',
+        // Push bold, text 0+5, pop bold, stop
+        0x02, 0x01, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00,
+    ];
+
+    const HELLO_CODE: [Instruction; 4] = [
+        Instruction::Push(StyleVar::Bold),
+        Instruction::Text(AddressRange {
+            base: Address(0),
+            range: 5,
+        }),
+        Instruction::Pop(StyleVar::Bold),
+        Instruction::Stop,
+    ];
+
+    #[test]
+    fn test_view() {
+        let view = ProgramView::new(&HELLO).unwrap();
+        assert_eq!(view.encoding(), CodeEncoding::Compact);
+        assert!(!view.is_utf8());
+        assert_eq!(view.text(), "Hello");
+        let mut instructions = view.instructions();
+        for expected in HELLO_CODE {
+            assert_eq!(instructions.next(), Some(Ok(expected)));
+        }
+        assert_eq!(instructions.next(), None);
+
+        let err = ProgramView::new(&HELLO[..HELLO.len() - 1]).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::SectionOutOfBounds { .. }));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_view_with_scratch() {
+        let program = Program {
+            text: Text::from(String::from("Hello Hello Hello")),
+            code: vec![
+                Instruction::Text(AddressRange {
+                    base: Address(6),
+                    range: 5,
+                }),
+                Instruction::Stop,
+            ],
+            ..Default::default()
+        };
+        let bytes = program
+            .to_binary()
+            .with_compression(TextCompression::Lzss)
+            .into_byte_buffer();
+
+        let err = ProgramView::new(&bytes).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ScratchTooSmall { needed: 17 });
+
+        let mut scratch = [0; 32];
+        assert_eq!(ProgramView::scratch_len(&bytes).unwrap(), 17);
+        let view = ProgramView::with_scratch(&bytes, &mut scratch).unwrap();
+        let mut instructions = view.instructions();
+        match instructions.next() {
+            Some(Ok(Instruction::Text(range))) => assert_eq!(view.get(range), Some("Hello")),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(instructions.next(), Some(Ok(Instruction::Stop)));
+        assert_eq!(instructions.next(), None);
+    }
+}